@@ -0,0 +1,86 @@
+-- The tables the API was written against before it kept its own migrations.
+
+CREATE TABLE players (
+    player_id BIGINT UNSIGNED NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    -- Ranks search results, bumped by whatever keeps track of how active players are.
+    search_relevance INT NOT NULL DEFAULT 0,
+    PRIMARY KEY (player_id),
+    FULLTEXT KEY idx_players__name (name)
+);
+
+CREATE TABLE modes (
+    mode_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name VARCHAR(32) NOT NULL,
+    short_name VARCHAR(16) NOT NULL,
+    PRIMARY KEY (mode_id),
+    UNIQUE KEY idx_modes__shortname (short_name)
+);
+
+INSERT INTO modes (mode_id, name, short_name) VALUES
+    (1, 'KZTimer', 'KZT'),
+    (2, 'SimpleKZ', 'SKZ'),
+    (3, 'Vanilla', 'VNL');
+
+CREATE TABLE maps (
+    map_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name VARCHAR(64) NOT NULL,
+    -- The name split into words, MySQL's fulltext parser doesn't split on underscores.
+    search_tags VARCHAR(255) NOT NULL DEFAULT '',
+    validated BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (map_id),
+    UNIQUE KEY idx_maps__name (name),
+    FULLTEXT KEY idx_maps__searchtags (search_tags)
+);
+
+CREATE TABLE mappers (
+    mapper_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    map_id INT UNSIGNED NOT NULL,
+    player_id BIGINT UNSIGNED NOT NULL,
+    PRIMARY KEY (mapper_id),
+    UNIQUE KEY idx_mappers__mapid_playerid (map_id, player_id),
+    KEY idx_mappers__playerid (player_id)
+);
+
+CREATE TABLE courses (
+    course_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    map_id INT UNSIGNED NOT NULL,
+    num INT UNSIGNED NOT NULL,
+    PRIMARY KEY (course_id),
+    UNIQUE KEY idx_courses__mapid_num (map_id, num)
+);
+
+-- A course in a mode, which is what leaderboards are kept for.
+CREATE TABLE filters (
+    filter_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    course_id INT UNSIGNED NOT NULL,
+    mode_id INT UNSIGNED NOT NULL,
+    nub_tier TINYINT UNSIGNED NULL,
+    pro_tier TINYINT UNSIGNED NULL,
+    PRIMARY KEY (filter_id),
+    UNIQUE KEY idx_filters__courseid_modeid (course_id, mode_id),
+    KEY idx_filters__modeid (mode_id)
+);
+
+CREATE TABLE servers (
+    server_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    token VARCHAR(64) NOT NULL,
+    PRIMARY KEY (server_id)
+);
+
+-- The two filterid indexes back the NUB and PRO leaderboards and are forced with USE INDEX.
+CREATE TABLE runs (
+    run_id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    player_id BIGINT UNSIGNED NOT NULL,
+    filter_id INT UNSIGNED NOT NULL,
+    server_id INT UNSIGNED NULL,
+    ticks INT UNSIGNED NOT NULL,
+    teleports INT UNSIGNED NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (run_id),
+    KEY idx_runs__filterid_playerid_ticks_createdat (filter_id, player_id, ticks, created_at),
+    KEY idx_runs__filterid_tps_playerid_ticks_createdat (filter_id, teleports, player_id, ticks, created_at),
+    KEY idx_runs__playerid_filterid (player_id, filter_id),
+    KEY idx_runs__createdat (created_at)
+);
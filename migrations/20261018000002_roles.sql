@@ -0,0 +1,42 @@
+CREATE TABLE roles (
+    role_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    name VARCHAR(32) NOT NULL,
+    PRIMARY KEY (role_id),
+    UNIQUE KEY idx_roles__name (name)
+);
+
+CREATE TABLE role_permissions (
+    role_id INT UNSIGNED NOT NULL,
+    permission VARCHAR(32) NOT NULL,
+    PRIMARY KEY (role_id, permission),
+    CONSTRAINT fk_role_permissions__roleid FOREIGN KEY (role_id) REFERENCES roles (role_id) ON DELETE CASCADE
+);
+
+CREATE TABLE player_roles (
+    player_id BIGINT UNSIGNED NOT NULL,
+    role_id INT UNSIGNED NOT NULL,
+    granted_by BIGINT UNSIGNED NULL,
+    granted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (player_id, role_id),
+    CONSTRAINT fk_player_roles__roleid FOREIGN KEY (role_id) REFERENCES roles (role_id) ON DELETE CASCADE
+);
+
+INSERT INTO roles (name) VALUES ('admin'), ('moderator'), ('map_reviewer');
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT r.role_id, p.permission
+FROM roles r
+INNER JOIN (
+    SELECT 'admin' AS role, 'ViewBans' AS permission
+    UNION ALL SELECT 'admin', 'ViewMaps'
+    UNION ALL SELECT 'admin', 'ManageBans'
+    UNION ALL SELECT 'admin', 'ApproveMaps'
+    UNION ALL SELECT 'admin', 'ManageServers'
+    UNION ALL SELECT 'admin', 'InvalidateRuns'
+    UNION ALL SELECT 'admin', 'ManageRoles'
+    UNION ALL SELECT 'moderator', 'ViewBans'
+    UNION ALL SELECT 'moderator', 'ManageBans'
+    UNION ALL SELECT 'moderator', 'InvalidateRuns'
+    UNION ALL SELECT 'map_reviewer', 'ViewMaps'
+    UNION ALL SELECT 'map_reviewer', 'ApproveMaps'
+) p ON p.role = r.name;
@@ -0,0 +1,15 @@
+CREATE TABLE user_sessions (
+    session_id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    player_id BIGINT UNSIGNED NOT NULL,
+    refresh_token_hash CHAR(64) NOT NULL,
+    -- The token the last refresh replaced, presenting it again revokes the session.
+    previous_refresh_token_hash CHAR(64) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    refreshed_at TIMESTAMP NULL,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP NULL,
+    PRIMARY KEY (session_id),
+    UNIQUE KEY idx_user_sessions__refreshtokenhash (refresh_token_hash),
+    KEY idx_user_sessions__previousrefreshtokenhash (previous_refresh_token_hash),
+    KEY idx_user_sessions__playerid (player_id)
+);
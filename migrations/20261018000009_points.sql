@@ -0,0 +1,29 @@
+-- Points each player earns on a filter, rewritten whenever the filter's leaderboard changes.
+CREATE TABLE course_points (
+    filter_id INT UNSIGNED NOT NULL,
+    kind ENUM('NUB', 'PRO') NOT NULL,
+    player_id BIGINT UNSIGNED NOT NULL,
+    mode_id INT UNSIGNED NOT NULL,
+    placement INT UNSIGNED NOT NULL,
+    ticks INT UNSIGNED NOT NULL,
+    points INT UNSIGNED NOT NULL,
+    PRIMARY KEY (filter_id, kind, player_id),
+    KEY idx_course_points__playerid_modeid_kind (player_id, mode_id, kind)
+);
+
+-- Per player totals of course_points, so the ladder doesn't have to aggregate on every request.
+CREATE TABLE player_points (
+    mode_id INT UNSIGNED NOT NULL,
+    kind ENUM('NUB', 'PRO') NOT NULL,
+    player_id BIGINT UNSIGNED NOT NULL,
+    points INT UNSIGNED NOT NULL,
+    courses INT UNSIGNED NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (mode_id, kind, player_id),
+    KEY idx_player_points__modeid_kind_points (mode_id, kind, points)
+);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT r.role_id, 'ManageRankings'
+FROM roles r
+WHERE r.name = 'admin';
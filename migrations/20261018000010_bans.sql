@@ -0,0 +1,13 @@
+CREATE TABLE bans (
+    ban_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    player_id BIGINT UNSIGNED NOT NULL,
+    ban_type VARCHAR(32) NOT NULL,
+    reason VARCHAR(255) NOT NULL,
+    notes TEXT NULL,
+    banned_by BIGINT UNSIGNED NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NULL,
+    PRIMARY KEY (ban_id),
+    KEY idx_bans__playerid_expiresat (player_id, expires_at)
+);
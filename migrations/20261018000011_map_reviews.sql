@@ -0,0 +1,16 @@
+ALTER TABLE maps
+    ADD COLUMN workshop_id BIGINT UNSIGNED NULL,
+    ADD COLUMN submitted_by BIGINT UNSIGNED NULL,
+    ADD COLUMN rejection_reason VARCHAR(255) NULL;
+
+-- Audit trail of everything that happened to a map's approval.
+CREATE TABLE map_reviews (
+    review_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    map_id INT UNSIGNED NOT NULL,
+    player_id BIGINT UNSIGNED NOT NULL,
+    action ENUM('submit', 'validate', 'reject', 'unvalidate') NOT NULL,
+    reason VARCHAR(255) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (review_id),
+    KEY idx_map_reviews__mapid_createdat (map_id, created_at)
+);
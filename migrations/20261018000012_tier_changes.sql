@@ -0,0 +1,12 @@
+CREATE TABLE tier_changes (
+    change_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    filter_id INT UNSIGNED NOT NULL,
+    kind ENUM('NUB', 'PRO') NOT NULL,
+    old_tier TINYINT UNSIGNED NULL,
+    new_tier TINYINT UNSIGNED NULL,
+    changed_by BIGINT UNSIGNED NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (change_id),
+    KEY idx_tier_changes__createdat (created_at),
+    KEY idx_tier_changes__filterid_createdat (filter_id, created_at)
+);
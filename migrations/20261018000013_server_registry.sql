@@ -0,0 +1,16 @@
+ALTER TABLE servers
+    ADD COLUMN name VARCHAR(64) NOT NULL DEFAULT '',
+    ADD COLUMN owner_id BIGINT UNSIGNED NULL,
+    ADD COLUMN ip VARCHAR(45) NULL,
+    ADD COLUMN port SMALLINT UNSIGNED NULL,
+    ADD COLUMN region VARCHAR(16) NULL,
+    ADD COLUMN token_hash CHAR(64) NULL,
+    ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+-- Tokens are only kept hashed from now on, the same SHA-256 hex the API computes.
+UPDATE servers SET token_hash = SHA2(token, 256);
+
+ALTER TABLE servers
+    DROP COLUMN token,
+    ADD UNIQUE KEY idx_servers__tokenhash (token_hash);
@@ -0,0 +1,10 @@
+-- Latest heartbeat of every server, overwritten on each one.
+CREATE TABLE server_status (
+    server_id INT UNSIGNED NOT NULL,
+    map VARCHAR(64) NULL,
+    player_count SMALLINT UNSIGNED NOT NULL,
+    max_players SMALLINT UNSIGNED NOT NULL,
+    plugin_version VARCHAR(32) NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (server_id)
+);
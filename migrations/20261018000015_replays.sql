@@ -0,0 +1,9 @@
+-- Replay metadata, the files themselves live in the replay storage under `storage_key`.
+CREATE TABLE replays (
+    run_id BIGINT UNSIGNED NOT NULL,
+    size INT UNSIGNED NOT NULL,
+    sha256 CHAR(64) NOT NULL,
+    storage_key VARCHAR(128) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (run_id)
+);
@@ -0,0 +1,19 @@
+CREATE TABLE jumpstats (
+    jumpstat_id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    player_id BIGINT UNSIGNED NOT NULL,
+    mode_id INT UNSIGNED NOT NULL,
+    server_id INT UNSIGNED NULL,
+    jump_type VARCHAR(32) NOT NULL,
+    distance DOUBLE NOT NULL,
+    strafes TINYINT UNSIGNED NOT NULL,
+    sync DOUBLE NOT NULL,
+    pre_speed DOUBLE NOT NULL,
+    max_speed DOUBLE NOT NULL,
+    airtime INT UNSIGNED NOT NULL,
+    -- Jumped with the jump bound to a key, which makes the takeoff easier.
+    binded BOOLEAN NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (jumpstat_id),
+    KEY idx_jumpstats__modeid_jumptype_binded_distance (mode_id, jump_type, binded, distance),
+    KEY idx_jumpstats__playerid_modeid_jumptype_distance (player_id, mode_id, jump_type, distance)
+);
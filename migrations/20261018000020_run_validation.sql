@@ -0,0 +1,59 @@
+-- Fastest time a course can plausibly be finished in, overriding the global minimum.
+ALTER TABLE courses
+    ADD COLUMN min_ticks INT UNSIGNED NULL;
+
+-- Servers resend runs they aren't sure went through, keep only the first. The copies already
+-- stored go first, their replays move to the run that stays unless it has one of its own, in
+-- which case the copy's file is left in the storage. Reviews don't exist before this migration.
+UPDATE IGNORE replays rp
+INNER JOIN runs r ON r.run_id = rp.run_id
+INNER JOIN (
+    SELECT MIN(r2.run_id) AS run_id, r2.filter_id, r2.player_id, r2.ticks, r2.teleports, r2.created_at
+    FROM runs r2
+    GROUP BY r2.filter_id, r2.player_id, r2.ticks, r2.teleports, r2.created_at
+    HAVING COUNT(*) > 1
+) kept ON kept.filter_id = r.filter_id AND kept.player_id = r.player_id AND kept.ticks = r.ticks
+    AND kept.teleports = r.teleports AND kept.created_at = r.created_at
+SET rp.run_id = kept.run_id
+WHERE r.run_id > kept.run_id;
+
+DELETE rp
+FROM replays rp
+INNER JOIN runs r ON r.run_id = rp.run_id
+INNER JOIN (
+    SELECT MIN(r2.run_id) AS run_id, r2.filter_id, r2.player_id, r2.ticks, r2.teleports, r2.created_at
+    FROM runs r2
+    GROUP BY r2.filter_id, r2.player_id, r2.ticks, r2.teleports, r2.created_at
+    HAVING COUNT(*) > 1
+) kept ON kept.filter_id = r.filter_id AND kept.player_id = r.player_id AND kept.ticks = r.ticks
+    AND kept.teleports = r.teleports AND kept.created_at = r.created_at
+WHERE r.run_id > kept.run_id;
+
+DELETE r
+FROM runs r
+INNER JOIN (
+    SELECT MIN(r2.run_id) AS run_id, r2.filter_id, r2.player_id, r2.ticks, r2.teleports, r2.created_at
+    FROM runs r2
+    GROUP BY r2.filter_id, r2.player_id, r2.ticks, r2.teleports, r2.created_at
+    HAVING COUNT(*) > 1
+) kept ON kept.filter_id = r.filter_id AND kept.player_id = r.player_id AND kept.ticks = r.ticks
+    AND kept.teleports = r.teleports AND kept.created_at = r.created_at
+WHERE r.run_id > kept.run_id;
+
+ALTER TABLE runs
+    ADD UNIQUE KEY idx_runs__filterid_playerid_ticks_tps_createdat (filter_id, player_id, ticks, teleports, created_at);
+
+-- Runs that looked suspicious on submission and wait for an admin to look at them.
+CREATE TABLE run_reviews (
+    review_id INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    run_id BIGINT UNSIGNED NOT NULL,
+    reason VARCHAR(255) NOT NULL,
+    status ENUM('pending', 'approved', 'rejected') NOT NULL DEFAULT 'pending',
+    reviewed_by BIGINT UNSIGNED NULL,
+    reviewed_at TIMESTAMP NULL,
+    notes VARCHAR(255) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (review_id),
+    KEY idx_run_reviews__status_createdat (status, created_at),
+    KEY idx_run_reviews__runid (run_id)
+);
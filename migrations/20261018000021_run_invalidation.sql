@@ -0,0 +1,5 @@
+-- Invalidated runs stay in the table but are left out of every leaderboard.
+ALTER TABLE runs
+    ADD COLUMN invalidated_at TIMESTAMP NULL,
+    ADD COLUMN invalidated_by BIGINT UNSIGNED NULL,
+    ADD COLUMN invalidation_reason VARCHAR(255) NULL;
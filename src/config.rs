@@ -0,0 +1,317 @@
+use anyhow::{bail, ensure, Context};
+use clap::{Args, Parser, Subcommand};
+use log::LevelFilter;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(version, about = "Leaderboards and player stats API")]
+pub struct Cli {
+    /// TOML file to read options from, using the flag names with underscores. Flags and
+    /// environment variables take precedence over it.
+    #[arg(long, env = "API_CONFIG")]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    options: Options,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum Command {
+    /// Applies pending migrations and exits.
+    Migrate {
+        /// Adopts a database created before the API kept its own migrations: checks its tables
+        /// match the initial migration and records that one as applied instead of running it.
+        #[arg(long)]
+        baseline: bool,
+    },
+}
+
+/// Every option, as given on the command line, in the environment or in the config file.
+/// Nothing here has a default so the three sources can be layered; defaults and validation
+/// are applied afterwards.
+#[derive(Args, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+    /// Defaults to 50.
+    #[arg(long, env = "DB_MAX_CONNECTIONS")]
+    db_max_connections: Option<u32>,
+    /// How long to wait for a free connection. Defaults to 30.
+    #[arg(long, env = "DB_ACQUIRE_TIMEOUT_SECS")]
+    db_acquire_timeout_secs: Option<u64>,
+    /// Applies pending migrations before serving. Defaults to false.
+    #[arg(long, env = "MIGRATE_ON_STARTUP", value_parser = clap::builder::BoolishValueParser::new())]
+    migrate_on_startup: Option<bool>,
+    /// One of off, error, warn, info, debug or trace. RUST_LOG can refine it per module.
+    /// Defaults to info.
+    #[arg(long, env = "LOG_LEVEL")]
+    log_level: Option<String>,
+
+    /// Defaults to 0.0.0.0:9000.
+    #[arg(long, env = "BIND_ADDRESS")]
+    bind_address: Option<String>,
+    /// Defaults to the number of CPUs.
+    #[arg(long, env = "HTTP_WORKERS")]
+    http_workers: Option<usize>,
+    /// PEM certificate chain. Serves HTTPS when given together with the private key.
+    #[arg(long, env = "TLS_CERTIFICATE")]
+    tls_certificate: Option<PathBuf>,
+    /// PEM private key of the certificate.
+    #[arg(long, env = "TLS_PRIVATE_KEY")]
+    tls_private_key: Option<PathBuf>,
+    /// How long clients get to send a request's headers. Defaults to 5.
+    #[arg(long, env = "REQUEST_TIMEOUT_SECS")]
+    request_timeout_secs: Option<u64>,
+    /// Defaults to 5.
+    #[arg(long, env = "KEEP_ALIVE_SECS")]
+    keep_alive_secs: Option<u64>,
+    /// How long running requests get to finish on shutdown. Defaults to 30.
+    #[arg(long, env = "SHUTDOWN_TIMEOUT_SECS")]
+    shutdown_timeout_secs: Option<u64>,
+    /// Comma separated origins allowed to read public data from a browser. Any origin when unset.
+    #[arg(long, env = "CORS_PUBLIC_ORIGINS", value_delimiter = ',')]
+    cors_public_origins: Option<Vec<String>>,
+    /// Comma separated origins allowed to send user tokens or make changes from a browser.
+    /// Defaults to the origin of the Steam realm.
+    #[arg(long, env = "CORS_ALLOWED_ORIGINS", value_delimiter = ',')]
+    cors_allowed_origins: Option<Vec<String>>,
+    /// Comma separated methods browsers may use. GET and HEAD are always allowed.
+    /// Defaults to GET,POST,PUT,PATCH,DELETE.
+    #[arg(long, env = "CORS_ALLOWED_METHODS", value_delimiter = ',')]
+    cors_allowed_methods: Option<Vec<String>>,
+    /// Comma separated request headers browsers may send. Defaults to Content-Type,X-User-Token.
+    #[arg(long, env = "CORS_ALLOWED_HEADERS", value_delimiter = ',')]
+    cors_allowed_headers: Option<Vec<String>>,
+
+    /// At least 32 characters.
+    #[arg(long, env = "AUTH_TOKEN_SECRET", hide_env_values = true)]
+    auth_token_secret: Option<String>,
+    /// Where the website lives, Steam sends users back there. Defaults to http://localhost:5000.
+    #[arg(long, env = "STEAM_REALM")]
+    steam_realm: Option<String>,
+    /// Defaults to 2, at most a week.
+    #[arg(long, env = "ACCESS_TOKEN_LIFETIME_HOURS")]
+    access_token_lifetime_hours: Option<i64>,
+    /// Defaults to 30, at most a year.
+    #[arg(long, env = "REFRESH_TOKEN_LIFETIME_DAYS")]
+    refresh_token_lifetime_days: Option<i64>,
+
+    /// Defaults to ./replays.
+    #[arg(long, env = "REPLAY_DIR")]
+    replay_dir: Option<PathBuf>,
+    /// Fastest time in ticks a course without its own minimum can be finished in.
+    /// Defaults to 128.
+    #[arg(long, env = "RUN_MIN_TICKS")]
+    run_min_ticks: Option<u32>,
+    /// Records improved by more than this fraction of the previous one are flagged for review.
+    /// Defaults to 0.1.
+    #[arg(long, env = "RUN_MAX_RECORD_IMPROVEMENT")]
+    run_max_record_improvement: Option<f64>,
+}
+
+impl Options {
+    /// Fills in whatever `self` is missing from `other`.
+    fn or(self, other: Options) -> Options {
+        Options {
+            database_url: self.database_url.or(other.database_url),
+            db_max_connections: self.db_max_connections.or(other.db_max_connections),
+            db_acquire_timeout_secs: self.db_acquire_timeout_secs.or(other.db_acquire_timeout_secs),
+            migrate_on_startup: self.migrate_on_startup.or(other.migrate_on_startup),
+            log_level: self.log_level.or(other.log_level),
+            bind_address: self.bind_address.or(other.bind_address),
+            http_workers: self.http_workers.or(other.http_workers),
+            tls_certificate: self.tls_certificate.or(other.tls_certificate),
+            tls_private_key: self.tls_private_key.or(other.tls_private_key),
+            request_timeout_secs: self.request_timeout_secs.or(other.request_timeout_secs),
+            keep_alive_secs: self.keep_alive_secs.or(other.keep_alive_secs),
+            shutdown_timeout_secs: self.shutdown_timeout_secs.or(other.shutdown_timeout_secs),
+            cors_public_origins: self.cors_public_origins.or(other.cors_public_origins),
+            cors_allowed_origins: self.cors_allowed_origins.or(other.cors_allowed_origins),
+            cors_allowed_methods: self.cors_allowed_methods.or(other.cors_allowed_methods),
+            cors_allowed_headers: self.cors_allowed_headers.or(other.cors_allowed_headers),
+            auth_token_secret: self.auth_token_secret.or(other.auth_token_secret),
+            steam_realm: self.steam_realm.or(other.steam_realm),
+            access_token_lifetime_hours: self.access_token_lifetime_hours.or(other.access_token_lifetime_hours),
+            refresh_token_lifetime_days: self.refresh_token_lifetime_days.or(other.refresh_token_lifetime_days),
+            replay_dir: self.replay_dir.or(other.replay_dir),
+            run_min_ticks: self.run_min_ticks.or(other.run_min_ticks),
+            run_max_record_improvement: self.run_max_record_improvement.or(other.run_max_record_improvement),
+        }
+    }
+}
+
+/// The options of every source layered on top of each other. Each part is validated when
+/// it's asked for, so `api migrate` doesn't need the HTTP settings.
+pub struct Config {
+    options: Options,
+}
+
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub acquire_timeout: Duration,
+    pub migrate_on_startup: bool,
+}
+
+pub struct HttpConfig {
+    pub bind_address: SocketAddr,
+    pub workers: Option<usize>,
+    pub tls: Option<TlsConfig>,
+    pub request_timeout: Duration,
+    pub keep_alive: Duration,
+    pub shutdown_timeout: Duration,
+    /// Origins allowed to read public data, `None` allows any.
+    pub cors_public_origins: Option<Vec<String>>,
+    /// Origins allowed to do everything else.
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allowed_methods: Vec<String>,
+    pub cors_allowed_headers: Vec<String>,
+    pub auth_token_secret: String,
+    pub steam_realm: String,
+    pub access_token_lifetime: chrono::Duration,
+    pub refresh_token_lifetime: chrono::Duration,
+    pub replay_dir: PathBuf,
+    pub run_min_ticks: u32,
+    pub run_max_record_improvement: f64,
+}
+
+pub struct TlsConfig {
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+}
+
+impl Config {
+    pub fn load(cli: Cli) -> anyhow::Result<(Option<Command>, Config)> {
+        let file = match &cli.config {
+            Some(path) => read_file(path)?,
+            None => Options::default(),
+        };
+        Ok((cli.command, Config { options: cli.options.or(file) }))
+    }
+
+    pub fn log_level(&self) -> anyhow::Result<LevelFilter> {
+        match &self.options.log_level {
+            Some(level) => level.parse()
+                .map_err(|_| anyhow::anyhow!("log_level must be one of off, error, warn, info, debug or trace, not {level}")),
+            None => Ok(LevelFilter::Info),
+        }
+    }
+
+    pub fn database(&self) -> anyhow::Result<DatabaseConfig> {
+        let o = &self.options;
+        let Some(url) = o.database_url.clone() else {
+            bail!("database_url is required, set it with --database-url, DATABASE_URL or the config file");
+        };
+        let max_connections = o.db_max_connections.unwrap_or(50);
+        ensure!(max_connections > 0, "db_max_connections must be at least 1");
+
+        Ok(DatabaseConfig {
+            url,
+            max_connections,
+            acquire_timeout: Duration::from_secs(o.db_acquire_timeout_secs.unwrap_or(30)),
+            migrate_on_startup: o.migrate_on_startup.unwrap_or(false),
+        })
+    }
+
+    pub fn http(&self) -> anyhow::Result<HttpConfig> {
+        let o = &self.options;
+        let bind_address = o.bind_address.as_deref().unwrap_or("0.0.0.0:9000");
+        let bind_address: SocketAddr = bind_address.parse()
+            .with_context(|| format!("bind_address must be an ip:port pair, not {bind_address}"))?;
+        ensure!(o.http_workers != Some(0), "http_workers must be at least 1");
+
+        let tls = match (&o.tls_certificate, &o.tls_private_key) {
+            (Some(certificate), Some(private_key)) => {
+                for path in [certificate, private_key] {
+                    ensure!(path.is_file(), "TLS file {} does not exist", path.display());
+                }
+                Some(TlsConfig {
+                    certificate: certificate.clone(),
+                    private_key: private_key.clone(),
+                })
+            }
+            (None, None) => None,
+            _ => bail!("tls_certificate and tls_private_key must be given together"),
+        };
+
+        let Some(auth_token_secret) = o.auth_token_secret.clone() else {
+            bail!("auth_token_secret is required, set it with --auth-token-secret, AUTH_TOKEN_SECRET or the config file");
+        };
+        ensure!(auth_token_secret.len() >= 32, "auth_token_secret must be at least 32 characters");
+        let steam_realm = o.steam_realm.clone().unwrap_or_else(|| "http://localhost:5000".to_owned());
+        ensure!(
+            steam_realm.starts_with("http://") || steam_realm.starts_with("https://"),
+            "steam_realm must be an http:// or https:// URL, not {steam_realm}",
+        );
+        let cors_allowed_origins = o.cors_allowed_origins.clone()
+            .unwrap_or_else(|| vec![origin_of(&steam_realm).to_owned()]);
+        for origin in o.cors_public_origins.iter().flatten().chain(&cors_allowed_origins) {
+            ensure!(
+                (origin.starts_with("http://") || origin.starts_with("https://")) && origin_of(origin) == origin,
+                "CORS origins must look like https://example.com, not {origin}",
+            );
+        }
+        let access_token_lifetime_hours = o.access_token_lifetime_hours.unwrap_or(2);
+        let refresh_token_lifetime_days = o.refresh_token_lifetime_days.unwrap_or(30);
+        // chrono panics on durations it can't represent, and tokens living for ages are a mistake anyway.
+        ensure!(
+            (1..=24 * 7).contains(&access_token_lifetime_hours),
+            "access_token_lifetime_hours must be between 1 and 168",
+        );
+        ensure!(
+            (1..=365).contains(&refresh_token_lifetime_days),
+            "refresh_token_lifetime_days must be between 1 and 365",
+        );
+
+        let run_max_record_improvement = o.run_max_record_improvement.unwrap_or(0.1);
+        ensure!(
+            run_max_record_improvement > 0.0 && run_max_record_improvement <= 1.0,
+            "run_max_record_improvement must be a fraction between 0 and 1",
+        );
+
+        Ok(HttpConfig {
+            bind_address,
+            workers: o.http_workers,
+            tls,
+            request_timeout: Duration::from_secs(o.request_timeout_secs.unwrap_or(5)),
+            keep_alive: Duration::from_secs(o.keep_alive_secs.unwrap_or(5)),
+            shutdown_timeout: Duration::from_secs(o.shutdown_timeout_secs.unwrap_or(30)),
+            cors_public_origins: o.cors_public_origins.clone(),
+            cors_allowed_origins,
+            cors_allowed_methods: o.cors_allowed_methods.clone().unwrap_or_else(|| {
+                ["GET", "POST", "PUT", "PATCH", "DELETE"].map(str::to_owned).to_vec()
+            }),
+            cors_allowed_headers: o.cors_allowed_headers.clone().unwrap_or_else(|| {
+                ["Content-Type", "X-User-Token"].map(str::to_owned).to_vec()
+            }),
+            auth_token_secret,
+            steam_realm,
+            access_token_lifetime: chrono::Duration::hours(access_token_lifetime_hours),
+            refresh_token_lifetime: chrono::Duration::days(refresh_token_lifetime_days),
+            replay_dir: o.replay_dir.clone().unwrap_or_else(|| PathBuf::from("replays")),
+            run_min_ticks: o.run_min_ticks.unwrap_or(128),
+            run_max_record_improvement,
+        })
+    }
+}
+
+/// The scheme, host and port of an http(s) URL, which is what browsers send as `Origin`.
+fn origin_of(url: &str) -> &str {
+    let path = url.find("://").map_or(0, |i| i + 3);
+    match url[path..].find('/') {
+        Some(i) => &url[..path + i],
+        None => url,
+    }
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Options> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read config file {}", path.display()))?;
+    toml::from_str(&contents)
+        .with_context(|| format!("invalid config file {}", path.display()))
+}
@@ -1,11 +1,13 @@
 use actix_web::dev::Payload;
 use actix_web::web::Data;
-use actix_web::{FromRequest, HttpRequest, Result};
+use actix_web::{FromRequest, HttpRequest};
 use serde::{Deserialize, Serialize};
 use sqlx::mysql::MySqlPool;
 use sqlx::FromRow;
 use std::future::Future;
 use std::pin::Pin;
+use super::error::ApiError;
+use super::token::hash_token;
 
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Server {
@@ -13,21 +15,21 @@ pub struct Server {
 }
 
 impl Server {
-    pub fn id(self: &Self) -> u32 {
+    pub fn id(&self) -> u32 {
         self.id
     }
 }
 
 impl FromRequest for Server {
-    type Error = actix_web::Error;
+    type Error = ApiError;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
         let Some(token) = req.headers().get("X-Server-Token") else {
-            return Box::pin(async move { Err(actix_web::error::ErrorBadRequest("X-Server-Token header missing")) });
+            return Box::pin(async move { Err(ApiError::MissingToken("X-Server-Token")) });
         };
         let Ok(token) = token.to_str() else {
-            return Box::pin(async move { Err(actix_web::error::ErrorBadRequest("X-Server-Token must be ASCII")) });
+            return Box::pin(async move { Err(ApiError::InvalidToken("X-Server-Token must be ASCII")) });
         };
         let token = token.to_owned();
         let db = req.app_data::<Data<MySqlPool>>().unwrap().get_ref().to_owned();
@@ -35,13 +37,12 @@ impl FromRequest for Server {
             let server: Server = sqlx::query_as(r#"
                 SELECT s.server_id AS id
                 FROM servers s
-                WHERE s.token = ?
+                WHERE s.token_hash = ? AND NOT s.disabled
                 LIMIT 1
             "#)
-            .bind(token)
-            .fetch_optional(&db).await
-            .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?
-            .ok_or(actix_web::error::ErrorUnauthorized("X-Server-Token is invalid"))?;
+            .bind(hash_token(&token))
+            .fetch_optional(&db).await?
+            .ok_or(ApiError::InvalidToken("X-Server-Token is invalid"))?;
             Ok(server)
         })
     }
@@ -1,17 +1,24 @@
-use actix_web::web::{self, Data, Json, ServiceConfig};
-use actix_web::{get, FromRequest, Result, HttpResponse, HttpRequest};
+use actix_web::web::{self, Data, Json, Path, ServiceConfig};
+use actix_web::{delete, get, post, FromRequest, HttpResponse, HttpRequest};
 use chrono::{Duration, Utc};
-use futures::TryFutureExt;
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use std::{env, future::Future, pin::Pin};
+use sqlx::MySqlPool;
+use std::{future::Future, pin::Pin, str::FromStr};
 use steam_openid::SteamOpenId;
+use crate::config::HttpConfig;
+use super::error::{ApiError, ApiResult};
 use super::model::AuthUserResponse;
+use super::roles::fetch_permissions;
+use super::steamid::SteamId;
+use super::token::{generate_token, hash_token};
 
 pub fn config(conf: &mut ServiceConfig) {
-    conf.app_data(Data::new(LocalData::new()))
-        .service(steam_auth)
+    conf.service(steam_auth)
         .service(steam_auth_verify)
+        .service(steam_auth_refresh)
+        .service(logout)
+        .service(revoke_player_sessions)
         .service(get_protected);
 }
 
@@ -25,29 +32,113 @@ async fn steam_auth(data: Data<LocalData>) -> HttpResponse {
 }
 
 #[get("/steam_auth_verify")]
-async fn steam_auth_verify(req: HttpRequest, data: Data<LocalData>) -> Result<HttpResponse> {
+async fn steam_auth_verify(req: HttpRequest, data: Data<LocalData>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
     let steamid64 = data.steam_openid.verify(req.query_string()).await
-        .map_err(|_| actix_web::error::ErrorUnauthorized("Verification failed"))?;
-    const STEAMID64_BASE: u64 = 76561197960265728;
-    if steamid64 <= STEAMID64_BASE {
-        return Err(actix_web::error::ErrorInternalServerError("Steam oopsie, please send help"));
-    }
+        .map_err(|_| ApiError::SteamVerificationFailed)?;
+    let user_id = SteamId::from_steamid64(steamid64)
+        .ok_or_else(|| ApiError::Internal(format!("Steam returned an invalid SteamID64 {steamid64}")))?
+        .account_id();
+    let refresh_token = generate_token();
+    let session_id = sqlx::query(r#"
+        INSERT INTO user_sessions (player_id, refresh_token_hash, expires_at)
+        VALUES (?, ?, ?)
+    "#)
+    .bind(user_id)
+    .bind(hash_token(&refresh_token))
+    .bind(Utc::now() + data.refresh_token_lifetime)
+    .execute(db.get_ref()).await?
+    .last_insert_id();
+
+    let token = issue_token(&data, db.get_ref(), user_id, session_id).await?;
+    let result = AuthUserResponse {
+        player_id: user_id,
+        token,
+        refresh_token,
+    };
+    Ok(HttpResponse::Ok().json(result))
+}
 
-    let user_id = steamid64 - STEAMID64_BASE;
-    let permissions = Vec::new();
-    let claims = Claims::new(user_id, permissions, Duration::hours(2));
-    let token = jsonwebtoken::encode(&Header::default(), &claims, &data.encoding_key)
-        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to encode token"))?;
+#[derive(Deserialize)]
+struct RefreshToken {
+    refresh_token: String,
+}
+
+#[post("/steam_auth_refresh")]
+async fn steam_auth_refresh(body: Json<RefreshToken>, data: Data<LocalData>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    let hash = hash_token(&body.refresh_token);
+    let session: Option<(u64, u64)> = sqlx::query_as(r#"
+        SELECT s.session_id, s.player_id
+        FROM user_sessions s
+        WHERE s.refresh_token_hash = ? AND s.revoked_at IS NULL AND s.expires_at > NOW()
+        LIMIT 1
+    "#)
+    .bind(&hash)
+    .fetch_optional(db.get_ref()).await?;
+    let Some((session_id, user_id)) = session else {
+        // A token that was already rotated out has been copied, whoever holds the current one
+        // can't be told apart from whoever stole it, so the session goes.
+        sqlx::query("UPDATE user_sessions SET revoked_at = NOW() WHERE previous_refresh_token_hash = ? AND revoked_at IS NULL")
+            .bind(&hash)
+            .execute(db.get_ref()).await?;
+        return Err(ApiError::InvalidToken("refresh token is invalid"));
+    };
+
+    // Refresh tokens are single use, every refresh hands out a new one and extends the session.
+    // Only the request that still finds the old hash in place gets to rotate it.
+    let refresh_token = generate_token();
+    let rotated = sqlx::query(r#"
+        UPDATE user_sessions
+        SET previous_refresh_token_hash = refresh_token_hash, refresh_token_hash = ?,
+            refreshed_at = NOW(), expires_at = ?
+        WHERE session_id = ? AND refresh_token_hash = ? AND revoked_at IS NULL
+    "#)
+    .bind(hash_token(&refresh_token))
+    .bind(Utc::now() + data.refresh_token_lifetime)
+    .bind(session_id)
+    .bind(&hash)
+    .execute(db.get_ref()).await?
+    .rows_affected();
+    if rotated != 1 {
+        return Err(ApiError::InvalidToken("refresh token is invalid"));
+    }
 
+    let token = issue_token(&data, db.get_ref(), user_id, session_id).await?;
     let result = AuthUserResponse {
         player_id: user_id,
         token,
+        refresh_token,
     };
     Ok(HttpResponse::Ok().json(result))
 }
 
+#[post("/logout")]
+async fn logout(user: User, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    sqlx::query("UPDATE user_sessions SET revoked_at = NOW() WHERE session_id = ?")
+        .bind(user.session_id)
+        .execute(db.get_ref()).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/players/{player_id}/sessions")]
+async fn revoke_player_sessions(user: User, path: Path<u64>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ManageRoles))?;
+    sqlx::query("UPDATE user_sessions SET revoked_at = NOW() WHERE player_id = ? AND revoked_at IS NULL")
+        .bind(path.into_inner())
+        .execute(db.get_ref()).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn issue_token(data: &LocalData, db: &MySqlPool, user_id: u64, session_id: u64) -> ApiResult<String> {
+    let permissions = fetch_permissions(db, user_id).await?;
+    let claims = Claims::new(user_id, session_id, permissions, data.access_token_lifetime);
+    jsonwebtoken::encode(&Header::default(), &claims, &data.encoding_key)
+        .map_err(|e| ApiError::Internal(format!("failed to encode token: {e}")))
+}
+
 #[get("/protected")]
-async fn get_protected(user: User) -> Result<Json<Vec<Permission>>> {
+async fn get_protected(user: User) -> ApiResult<Json<Vec<Permission>>> {
     user_guard(user.has_permission(Permission::ViewBans))?;
     Ok(web::Json(user.permissions))
 }
@@ -56,19 +147,62 @@ async fn get_protected(user: User) -> Result<Json<Vec<Permission>>> {
 pub enum Permission {
     ViewBans,
     ViewMaps,
+    ManageBans,
+    ApproveMaps,
+    ManageServers,
+    InvalidateRuns,
+    ManageRoles,
+    ManageRankings,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::ViewBans,
+        Permission::ViewMaps,
+        Permission::ManageBans,
+        Permission::ApproveMaps,
+        Permission::ManageServers,
+        Permission::InvalidateRuns,
+        Permission::ManageRoles,
+        Permission::ManageRankings,
+    ];
+
+    /// The name used in `role_permissions.permission`, same as the serialized form.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ViewBans => "ViewBans",
+            Permission::ViewMaps => "ViewMaps",
+            Permission::ManageBans => "ManageBans",
+            Permission::ApproveMaps => "ApproveMaps",
+            Permission::ManageServers => "ManageServers",
+            Permission::InvalidateRuns => "InvalidateRuns",
+            Permission::ManageRoles => "ManageRoles",
+            Permission::ManageRankings => "ManageRankings",
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Permission::ALL.into_iter().find(|p| p.as_str() == s).ok_or(())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 struct Claims {
     user_id: u64,
+    session_id: u64,
     permissions: Vec<Permission>,
     exp: i64,
 }
 
 impl Claims {
-    fn new(user_id: u64, permissions: Vec<Permission>, valid_for: Duration) -> Self {
+    fn new(user_id: u64, session_id: u64, permissions: Vec<Permission>, valid_for: Duration) -> Self {
         Self {
             user_id,
+            session_id,
             permissions,
             exp: (Utc::now() + valid_for).timestamp(),
         }
@@ -77,65 +211,90 @@ impl Claims {
 
 pub struct User {
     id: u64,
+    session_id: u64,
     permissions: Vec<Permission>,
 }
 
 impl User {
-    fn new(id: u64, permissions: Vec<Permission>) -> Self {
+    fn new(id: u64, session_id: u64, permissions: Vec<Permission>) -> Self {
         Self {
             id,
+            session_id,
             permissions,
         }
     }
-    pub fn id(self: &Self) -> u64 {
+    pub fn id(&self) -> u64 {
         self.id
     }
-    pub fn has_permission(self: &Self, p: Permission) -> bool {
+    pub fn has_permission(&self, p: Permission) -> bool {
         self.permissions.contains(&p)
     }
 }
 
 impl FromRequest for User {
-    type Error = actix_web::Error;
+    type Error = ApiError;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &actix_web::HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
         let Some(token) = req.headers().get("X-User-Token") else {
-            return Box::pin(async move { Err(actix_web::error::ErrorBadRequest("X-User-Token header missing")) });
+            return Box::pin(async move { Err(ApiError::MissingToken("X-User-Token")) });
         };
         let Ok(token) = token.to_str() else {
-            return Box::pin(async move { Err(actix_web::error::ErrorBadRequest("X-User-Token must be ASCII")) });
+            return Box::pin(async move { Err(ApiError::InvalidToken("X-User-Token must be ASCII")) });
         };
         let data = req.app_data::<Data<LocalData>>().unwrap();
-        let Ok(decoded) = jsonwebtoken::decode::<Claims>(&token, &data.decoding_key, &Validation::default()) else {
-            return Box::pin(async move { Err(actix_web::error::ErrorUnauthorized("X-User-Token is invalid")) });
+        let Ok(decoded) = jsonwebtoken::decode::<Claims>(token, &data.decoding_key, &Validation::default()) else {
+            return Box::pin(async move { Err(ApiError::InvalidToken("X-User-Token is invalid")) });
         };
-        Box::pin(async move { Ok(User::new(decoded.claims.user_id, decoded.claims.permissions)) })
+        let db = req.app_data::<Data<MySqlPool>>().unwrap().get_ref().to_owned();
+        Box::pin(async move {
+            let Claims { user_id, session_id, .. } = decoded.claims;
+            // The token alone isn't enough: the session may have been revoked since it was issued,
+            // and permissions are re-read so role changes apply without waiting for a refresh.
+            let active: Option<(u64,)> = sqlx::query_as(r#"
+                SELECT s.session_id
+                FROM user_sessions s
+                WHERE s.session_id = ? AND s.player_id = ? AND s.revoked_at IS NULL AND s.expires_at > NOW()
+                LIMIT 1
+            "#)
+            .bind(session_id)
+            .bind(user_id)
+            .fetch_optional(&db).await?;
+            if active.is_none() {
+                return Err(ApiError::InvalidToken("X-User-Token has been revoked"));
+            }
+            let permissions = fetch_permissions(&db, user_id).await?;
+            Ok(User::new(user_id, session_id, permissions))
+        })
     }
 }
 
-pub fn user_guard(condition: bool) -> Result<()> {
+pub fn user_guard(condition: bool) -> ApiResult<()> {
     if condition {
         Ok(())
     } else {
-        Err(actix_web::error::ErrorForbidden("forbidden"))
+        Err(ApiError::Forbidden)
     }
 }
 
-struct LocalData {
+pub(super) struct LocalData {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
     steam_openid: SteamOpenId,
+    access_token_lifetime: Duration,
+    refresh_token_lifetime: Duration,
 }
 
 impl LocalData {
-    fn new() -> Self {
-        let auth_token_secret = env::var("AUTH_TOKEN_SECRET").expect("AUTH_TOKEN_SECRET not set");
-        assert!(auth_token_secret.len() >= 32);
-        Self {
-            encoding_key: EncodingKey::from_secret(auth_token_secret.as_ref()),
-            decoding_key: DecodingKey::from_secret(auth_token_secret.as_ref()),
-            steam_openid: SteamOpenId::new("http://localhost:5000", "#/steam_auth").unwrap(),
-        }
+    pub(super) fn new(config: &HttpConfig) -> anyhow::Result<Self> {
+        let steam_openid = SteamOpenId::new(&config.steam_realm, "#/steam_auth")
+            .map_err(|e| anyhow::anyhow!("invalid steam_realm {}: {e:?}", config.steam_realm))?;
+        Ok(Self {
+            encoding_key: EncodingKey::from_secret(config.auth_token_secret.as_ref()),
+            decoding_key: DecodingKey::from_secret(config.auth_token_secret.as_ref()),
+            steam_openid,
+            access_token_lifetime: config.access_token_lifetime,
+            refresh_token_lifetime: config.refresh_token_lifetime,
+        })
     }
 }
@@ -0,0 +1,213 @@
+use actix_web::{get, patch, post};
+use actix_web::web::{ServiceConfig, Json, Data, Path, Query};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+use sqlx::MySqlPool;
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::model::Ban;
+use super::points::recompute_player;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(get_bans)
+        .service(create_ban)
+        .service(update_ban)
+        .service(expire_ban);
+}
+
+/// Condition that holds when the player of the runs aliased as `alias` has no active ban.
+pub fn not_banned(alias: &str) -> String {
+    format!(r#"NOT EXISTS (
+        SELECT 1 FROM bans bn
+        WHERE bn.player_id = {alias}.player_id AND (bn.expires_at IS NULL OR bn.expires_at > NOW())
+    )"#)
+}
+
+pub async fn is_banned(db: &MySqlPool, player_id: u64) -> ApiResult<bool> {
+    let ban: Option<(u32,)> = sqlx::query_as(r#"
+        SELECT b.ban_id
+        FROM bans b
+        WHERE b.player_id = ? AND (b.expires_at IS NULL OR b.expires_at > NOW())
+        LIMIT 1
+    "#)
+    .bind(player_id)
+    .fetch_optional(db).await?;
+    Ok(ban.is_some())
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum BanType {
+    BhopHack,
+    BhopMacro,
+    StrafeHack,
+    Exploit,
+    Other,
+}
+
+impl BanType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BanType::BhopHack => "bhop_hack",
+            BanType::BhopMacro => "bhop_macro",
+            BanType::StrafeHack => "strafe_hack",
+            BanType::Exploit => "exploit",
+            BanType::Other => "other",
+        }
+    }
+}
+
+const BAN_COLUMNS: &str = r#"
+    b.ban_id AS id, b.player_id, p.name AS player_name, b.ban_type, b.reason, b.notes,
+    b.banned_by, b.created_at, b.updated_at, b.expires_at,
+    (b.expires_at IS NULL OR b.expires_at > NOW()) AS active
+"#;
+
+async fn fetch_ban(db: &MySqlPool, ban_id: u32) -> ApiResult<Ban> {
+    let ban: Ban = sqlx::query_as(&format!(r#"
+        SELECT {BAN_COLUMNS}
+        FROM bans b
+        LEFT JOIN players p ON p.player_id = b.player_id
+        WHERE b.ban_id = ?
+    "#))
+    .bind(ban_id)
+    .fetch_optional(db).await?
+    .ok_or(ApiError::UnknownBan)?;
+    Ok(ban)
+}
+
+/// Points are computed without banned players, so they have to be redone when a ban starts or
+/// ends. Bans that simply run out are picked up the next time their filters are recomputed.
+/// That means every filter the player finished, far too many to wait for, so it carries on in
+/// the background.
+fn ban_changed(db: &MySqlPool, player_id: u64) {
+    let db = db.clone();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = recompute_player(&db, player_id).await {
+            log::error!("failed to recompute points of player {player_id}: {e:?}");
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct GetBans {
+    player_id: Option<u64>,
+    active: Option<bool>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+const BANS_DEFAULT_LIMIT: u64 = 50;
+const BANS_MAX_LIMIT: u64 = 200;
+
+#[get("/bans")]
+async fn get_bans(user: User, query: Query<GetBans>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Ban>>> {
+    user_guard(user.has_permission(Permission::ViewBans))?;
+    let mut conditions = String::from("1");
+    if let Some(player_id) = query.player_id {
+        conditions += &format!(" AND b.player_id = {player_id}");
+    }
+    match query.active {
+        Some(true) => conditions += " AND (b.expires_at IS NULL OR b.expires_at > NOW())",
+        Some(false) => conditions += " AND b.expires_at <= NOW()",
+        None => {}
+    }
+    let result: Vec<Ban> = sqlx::query_as(&format!(r#"
+        SELECT {BAN_COLUMNS}
+        FROM bans b
+        LEFT JOIN players p ON p.player_id = b.player_id
+        WHERE {conditions}
+        ORDER BY b.created_at DESC
+        LIMIT ? OFFSET ?
+    "#))
+    .bind(query.limit.unwrap_or(BANS_DEFAULT_LIMIT).clamp(1, BANS_MAX_LIMIT))
+    .bind(query.offset.unwrap_or(0))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct CreateBan {
+    player_id: u64,
+    ban_type: BanType,
+    reason: String,
+    notes: Option<String>,
+    /// `None` bans permanently.
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[post("/bans")]
+async fn create_ban(user: User, body: Json<CreateBan>, db: Data<MySqlPool>) -> ApiResult<Json<Ban>> {
+    user_guard(user.has_permission(Permission::ManageBans))?;
+    let ban_id = sqlx::query(r#"
+        INSERT INTO bans (player_id, ban_type, reason, notes, banned_by, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+    "#)
+    .bind(body.player_id)
+    .bind(body.ban_type.as_str())
+    .bind(&body.reason)
+    .bind(&body.notes)
+    .bind(user.id())
+    .bind(body.expires_at)
+    .execute(db.get_ref()).await?
+    .last_insert_id();
+
+    ban_changed(db.get_ref(), body.player_id);
+    Ok(Json(fetch_ban(db.get_ref(), ban_id as u32).await?))
+}
+
+/// Tells a missing field (`None`) apart from an explicit `null` (`Some(None)`).
+fn explicit<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+struct UpdateBan {
+    ban_type: Option<BanType>,
+    reason: Option<String>,
+    #[serde(default, deserialize_with = "explicit")]
+    notes: Option<Option<String>>,
+    /// `null` makes the ban permanent.
+    #[serde(default, deserialize_with = "explicit")]
+    expires_at: Option<Option<DateTime<Utc>>>,
+}
+
+#[patch("/bans/{ban_id}")]
+async fn update_ban(user: User, path: Path<u32>, body: Json<UpdateBan>, db: Data<MySqlPool>) -> ApiResult<Json<Ban>> {
+    user_guard(user.has_permission(Permission::ManageBans))?;
+    let ban = fetch_ban(db.get_ref(), path.into_inner()).await?;
+    sqlx::query(r#"
+        UPDATE bans
+        SET ban_type = ?, reason = ?, notes = ?, expires_at = ?
+        WHERE ban_id = ?
+    "#)
+    .bind(body.ban_type.map_or(ban.ban_type.as_str(), |t| t.as_str()))
+    .bind(body.reason.as_ref().unwrap_or(&ban.reason))
+    .bind(body.notes.as_ref().unwrap_or(&ban.notes))
+    .bind(body.expires_at.unwrap_or(ban.expires_at))
+    .bind(ban.id)
+    .execute(db.get_ref()).await?;
+
+    if body.expires_at.is_some() {
+        ban_changed(db.get_ref(), ban.player_id);
+    }
+    Ok(Json(fetch_ban(db.get_ref(), ban.id).await?))
+}
+
+#[post("/bans/{ban_id}/expire")]
+async fn expire_ban(user: User, path: Path<u32>, db: Data<MySqlPool>) -> ApiResult<Json<Ban>> {
+    user_guard(user.has_permission(Permission::ManageBans))?;
+    let ban = fetch_ban(db.get_ref(), path.into_inner()).await?;
+    if ban.active {
+        sqlx::query("UPDATE bans SET expires_at = NOW() WHERE ban_id = ?")
+            .bind(ban.id)
+            .execute(db.get_ref()).await?;
+        ban_changed(db.get_ref(), ban.player_id);
+    }
+    Ok(Json(fetch_ban(db.get_ref(), ban.id).await?))
+}
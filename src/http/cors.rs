@@ -0,0 +1,178 @@
+use actix_cors::Cors;
+use actix_web::dev::RequestHead;
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use anyhow::Context;
+use std::rc::Rc;
+use std::str::FromStr;
+use crate::config::HttpConfig;
+
+const USER_TOKEN: HeaderName = HeaderName::from_static("x-user-token");
+
+/// Which origins may call the API from a browser. Anyone can read the leaderboards, but
+/// requests carrying an `X-User-Token` or changing anything are only allowed from the
+/// sites that log users in, so a random page can't act on behalf of a logged in user.
+#[derive(Clone)]
+pub struct CorsPolicy {
+    /// `None` allows any origin.
+    public_origins: Option<Vec<HeaderValue>>,
+    origins: Vec<HeaderValue>,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+}
+
+impl CorsPolicy {
+    pub fn new(config: &HttpConfig) -> anyhow::Result<Self> {
+        let origin = |origin: &String| HeaderValue::from_str(origin)
+            .with_context(|| format!("invalid CORS origin {origin}"));
+        let public_origins = match &config.cors_public_origins {
+            Some(origins) => Some(origins.iter().map(origin).collect::<anyhow::Result<_>>()?),
+            None => None,
+        };
+        let origins = config.cors_allowed_origins.iter().map(origin).collect::<anyhow::Result<_>>()?;
+        let methods = config.cors_allowed_methods.iter()
+            .map(|method| Method::from_str(method).with_context(|| format!("invalid CORS method {method}")))
+            .collect::<anyhow::Result<_>>()?;
+        let headers = config.cors_allowed_headers.iter()
+            .map(|header| HeaderName::from_str(header).with_context(|| format!("invalid CORS header {header}")))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            public_origins,
+            origins,
+            methods,
+            headers,
+        })
+    }
+
+    /// The middleware enforcing the policy. It's built once per worker.
+    pub fn cors(&self) -> Cors {
+        let policy = Rc::new(self.clone());
+        Cors::default()
+            .allowed_origin_fn(move |origin, req| policy.allows(origin, req))
+            .allowed_methods(self.methods.iter().cloned().chain([Method::GET, Method::HEAD]))
+            .allowed_headers(self.headers.iter().cloned())
+            .max_age(3600)
+    }
+
+    fn allows(&self, origin: &HeaderValue, req: &RequestHead) -> bool {
+        if is_public_read(req) {
+            if let Some(public_origins) = &self.public_origins {
+                return public_origins.contains(origin);
+            }
+            return true;
+        }
+        self.origins.contains(origin)
+    }
+}
+
+/// Whether a request, or the one a preflight asks about, only reads public data: a GET
+/// without a user token. Everything else is held to the stricter list of origins.
+fn is_public_read(req: &RequestHead) -> bool {
+    let preflight = req.method == Method::OPTIONS
+        && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if !preflight {
+        return matches!(req.method, Method::GET | Method::HEAD) && !req.headers().contains_key(USER_TOKEN);
+    }
+
+    let method = req.headers().get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|method| Method::from_bytes(method.as_bytes()).ok());
+    let sends_token = req.headers().get_all(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .filter_map(|headers| headers.to_str().ok())
+        .flat_map(|headers| headers.split(','))
+        .any(|header| header.trim().eq_ignore_ascii_case(USER_TOKEN.as_str()));
+    matches!(method, Some(Method::GET | Method::HEAD)) && !sends_token
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use super::*;
+
+    fn policy(public_origins: Option<&[&str]>) -> CorsPolicy {
+        let values = |origins: &[&str]| origins.iter().map(|o| HeaderValue::from_str(o).unwrap()).collect();
+        CorsPolicy {
+            public_origins: public_origins.map(values),
+            origins: values(&["https://kz.example"]),
+            methods: vec![Method::GET, Method::POST, Method::DELETE],
+            headers: vec![header::CONTENT_TYPE, USER_TOKEN],
+        }
+    }
+
+    async fn status(policy: CorsPolicy, req: TestRequest) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .wrap(policy.cors())
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route("/", web::post().to(HttpResponse::Ok))
+        ).await;
+        test::call_service(&app, req.uri("/").to_request()).await.status()
+    }
+
+    fn preflight(origin: &str, method: &str, headers: Option<&str>) -> TestRequest {
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, method));
+        match headers {
+            Some(headers) => req.insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, headers)),
+            None => req,
+        }
+    }
+
+    #[actix_web::test]
+    async fn public_reads_allow_any_origin() {
+        let req = TestRequest::get().insert_header((header::ORIGIN, "https://elsewhere.example"));
+        assert_eq!(status(policy(None), req).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn public_reads_reject_unlisted_origins() {
+        let public = Some(&["https://kz.example", "https://stats.example"][..]);
+        let req = TestRequest::get().insert_header((header::ORIGIN, "https://stats.example"));
+        assert_eq!(status(policy(public), req).await, StatusCode::OK);
+        let req = TestRequest::get().insert_header((header::ORIGIN, "https://elsewhere.example"));
+        assert_eq!(status(policy(public), req).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn token_requests_reject_unlisted_origins() {
+        let req = TestRequest::get()
+            .insert_header((header::ORIGIN, "https://elsewhere.example"))
+            .insert_header((USER_TOKEN, "token"));
+        assert_eq!(status(policy(None), req).await, StatusCode::BAD_REQUEST);
+        let req = TestRequest::get()
+            .insert_header((header::ORIGIN, "https://kz.example"))
+            .insert_header((USER_TOKEN, "token"));
+        assert_eq!(status(policy(None), req).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn writes_reject_unlisted_origins() {
+        let req = TestRequest::post().insert_header((header::ORIGIN, "https://elsewhere.example"));
+        assert_eq!(status(policy(None), req).await, StatusCode::BAD_REQUEST);
+        let req = TestRequest::post().insert_header((header::ORIGIN, "https://kz.example"));
+        assert_eq!(status(policy(None), req).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn preflights_follow_the_request_they_ask_about() {
+        let req = preflight("https://elsewhere.example", "GET", Some("content-type"));
+        assert_eq!(status(policy(None), req).await, StatusCode::OK);
+        let req = preflight("https://elsewhere.example", "GET", Some("content-type, X-User-Token"));
+        assert_eq!(status(policy(None), req).await, StatusCode::BAD_REQUEST);
+        let req = preflight("https://elsewhere.example", "DELETE", None);
+        assert_eq!(status(policy(None), req).await, StatusCode::BAD_REQUEST);
+        let req = preflight("https://kz.example", "DELETE", Some("x-user-token"));
+        assert_eq!(status(policy(None), req).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn preflights_reject_unlisted_methods_and_headers() {
+        let req = preflight("https://kz.example", "PUT", None);
+        assert_eq!(status(policy(None), req).await, StatusCode::BAD_REQUEST);
+        let req = preflight("https://kz.example", "POST", Some("x-server-token"));
+        assert_eq!(status(policy(None), req).await, StatusCode::BAD_REQUEST);
+    }
+}
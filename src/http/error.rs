@@ -0,0 +1,158 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+use sqlx::mysql::MySqlDatabaseError;
+use std::fmt;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// Every error the API hands back to clients. The `code` of a variant is part of the API and
+/// must not change; the message is for humans and may.
+#[derive(Debug)]
+pub enum ApiError {
+    UnknownMap,
+    UnknownCourse,
+    UnknownMode,
+    UnknownFilter,
+    UnknownRole,
+    UnknownPlayer,
+    UnknownBan,
+    UnknownServer,
+    UnknownRun,
+    UnknownReview,
+    PlayerBanned,
+    MapExists,
+    DuplicateRun,
+    RecomputeInProgress,
+    ImplausibleRun(String),
+    NotFound(&'static str),
+    BadRequest(String),
+    MissingToken(&'static str),
+    InvalidToken(&'static str),
+    SteamVerificationFailed,
+    Forbidden,
+    Database(sqlx::Error),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::UnknownMap => "unknown_map",
+            ApiError::UnknownCourse => "unknown_course",
+            ApiError::UnknownMode => "unknown_mode",
+            ApiError::UnknownFilter => "course_not_available_in_mode",
+            ApiError::UnknownRole => "unknown_role",
+            ApiError::UnknownPlayer => "unknown_player",
+            ApiError::UnknownBan => "unknown_ban",
+            ApiError::UnknownServer => "unknown_server",
+            ApiError::UnknownRun => "unknown_run",
+            ApiError::UnknownReview => "unknown_review",
+            ApiError::PlayerBanned => "player_banned",
+            ApiError::MapExists => "map_exists",
+            ApiError::DuplicateRun => "duplicate_run",
+            ApiError::RecomputeInProgress => "recompute_in_progress",
+            ApiError::ImplausibleRun(_) => "implausible_run",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::MissingToken(_) => "missing_token",
+            ApiError::InvalidToken(_) => "invalid_token",
+            ApiError::SteamVerificationFailed => "steam_verification_failed",
+            ApiError::Forbidden => "forbidden",
+            ApiError::Database(_) | ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::UnknownMap => f.write_str("map not found"),
+            ApiError::UnknownCourse => f.write_str("course not found"),
+            ApiError::UnknownMode => f.write_str("mode not found"),
+            ApiError::UnknownFilter => f.write_str("course is not available in this mode"),
+            ApiError::UnknownRole => f.write_str("role not found"),
+            ApiError::UnknownPlayer => f.write_str("player not found"),
+            ApiError::UnknownBan => f.write_str("ban not found"),
+            ApiError::UnknownServer => f.write_str("server not found"),
+            ApiError::UnknownRun => f.write_str("run not found"),
+            ApiError::UnknownReview => f.write_str("review not found"),
+            ApiError::PlayerBanned => f.write_str("player is banned"),
+            ApiError::MapExists => f.write_str("a map with this name already exists"),
+            ApiError::DuplicateRun => f.write_str("this run was already submitted"),
+            ApiError::RecomputeInProgress => f.write_str("a recompute is already running"),
+            ApiError::ImplausibleRun(reason) => f.write_str(reason),
+            ApiError::NotFound(what) => f.write_str(what),
+            ApiError::BadRequest(reason) => f.write_str(reason),
+            ApiError::MissingToken(header) => write!(f, "{header} header missing"),
+            ApiError::InvalidToken(reason) => f.write_str(reason),
+            ApiError::SteamVerificationFailed => f.write_str("Steam verification failed"),
+            ApiError::Forbidden => f.write_str("forbidden"),
+            // Internals stay in the logs.
+            ApiError::Database(_) | ApiError::Internal(_) => f.write_str("internal server error"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::UnknownMap
+            | ApiError::UnknownCourse
+            | ApiError::UnknownMode
+            | ApiError::UnknownFilter
+            | ApiError::UnknownRole
+            | ApiError::UnknownPlayer
+            | ApiError::UnknownBan
+            | ApiError::UnknownServer
+            | ApiError::UnknownRun
+            | ApiError::UnknownReview
+            | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::MissingToken(_)
+            | ApiError::InvalidToken(_)
+            | ApiError::SteamVerificationFailed => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden | ApiError::PlayerBanned => StatusCode::FORBIDDEN,
+            ApiError::MapExists
+            | ApiError::DuplicateRun
+            | ApiError::RecomputeInProgress => StatusCode::CONFLICT,
+            ApiError::ImplausibleRun(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ApiError::Database(e) => log::error!("database error: {e}"),
+            ApiError::Internal(e) => log::error!("internal error: {e}"),
+            _ => {}
+        }
+        HttpResponse::build(self.status_code()).json(json!({
+            "error": self.code(),
+            "message": self.to_string(),
+        }))
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::Database(e)
+    }
+}
+
+/// Whether the statement failed on a unique key, MySQL's `ER_DUP_ENTRY`.
+pub fn is_duplicate_key(e: &sqlx::Error) -> bool {
+    mysql_error_number(e) == Some(1062)
+}
+
+/// Whether the transaction was rolled back to break a deadlock, MySQL's `ER_LOCK_DEADLOCK`.
+/// Running it again is all it takes.
+pub fn is_deadlock(e: &sqlx::Error) -> bool {
+    mysql_error_number(e) == Some(1213)
+}
+
+fn mysql_error_number(e: &sqlx::Error) -> Option<u16> {
+    e.as_database_error()
+        .and_then(|e| e.try_downcast_ref::<MySqlDatabaseError>())
+        .map(|e| e.number())
+}
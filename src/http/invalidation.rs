@@ -0,0 +1,170 @@
+use actix_web::{post, HttpResponse};
+use actix_web::web::{ServiceConfig, Json, Data, Path};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::{MySql, MySqlPool, Transaction};
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::lookup::{resolve_map, resolve_mode};
+use super::model::InvalidatedRuns;
+use super::points::recompute_filter;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(invalidate_course_runs)
+        .service(invalidate_run)
+        .service(restore_run);
+}
+
+/// Condition leaving out invalidated runs of the runs table aliased as `alias`. Every query
+/// feeding a leaderboard, a PB or points needs it.
+pub fn not_invalidated(alias: &str) -> String {
+    format!("{alias}.invalidated_at IS NULL")
+}
+
+/// Trims a moderation reason and checks it fits the `VARCHAR(255)` columns they're kept in.
+pub fn check_reason(reason: &str) -> ApiResult<&str> {
+    let reason = reason.trim();
+    if reason.is_empty() || reason.len() > 255 {
+        return Err(ApiError::BadRequest("reasons must be 1 to 255 characters".to_owned()));
+    }
+    Ok(reason)
+}
+
+/// Invalidates every valid run of the runs table aliased as `r` matching `conditions`, then
+/// recomputes the points of the filters they were on.
+pub async fn invalidate_runs(db: &MySqlPool, invalidated_by: u64, conditions: &str, reason: &str) -> ApiResult<u64> {
+    let mut tx = db.begin().await?;
+    let (invalidated, filters) = invalidate_runs_in(&mut tx, invalidated_by, conditions, reason).await?;
+    tx.commit().await?;
+
+    recompute_filters(db, &filters).await;
+    Ok(invalidated)
+}
+
+/// The part of `invalidate_runs` that goes into a transaction, for callers with more to do in
+/// it. Returns how many runs were invalidated and the filters to recompute once it commits.
+pub async fn invalidate_runs_in(tx: &mut Transaction<'_, MySql>, invalidated_by: u64, conditions: &str, reason: &str) -> ApiResult<(u64, Vec<u32>)> {
+    let not_invalidated = not_invalidated("r");
+    let filters: Vec<(u32,)> = sqlx::query_as(&format!(r#"
+        SELECT DISTINCT r.filter_id
+        FROM runs r
+        WHERE {conditions} AND {not_invalidated}
+        FOR UPDATE
+    "#))
+    .fetch_all(&mut *tx).await?;
+    let invalidated = sqlx::query(&format!(r#"
+        UPDATE runs r
+        SET r.invalidated_at = NOW(), r.invalidated_by = ?, r.invalidation_reason = ?
+        WHERE {conditions} AND {not_invalidated}
+    "#))
+    .bind(invalidated_by)
+    .bind(reason)
+    .execute(&mut *tx).await?
+    .rows_affected();
+
+    Ok((invalidated, filters.into_iter().map(|(filter_id,)| filter_id).collect()))
+}
+
+/// Brings the points of filters up to date after their runs changed. The change is committed
+/// at this point whether or not the points catch up.
+pub async fn recompute_filters(db: &MySqlPool, filters: &[u32]) {
+    for &filter_id in filters {
+        if let Err(e) = recompute_filter(db, filter_id).await {
+            log::error!("failed to recompute points of filter {filter_id}: {e:?}");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct InvalidateRun {
+    reason: String,
+}
+
+#[post("/runs/{run_id}/invalidate")]
+async fn invalidate_run(user: User, path: Path<u64>, body: Json<InvalidateRun>, db: Data<MySqlPool>) -> ApiResult<Json<InvalidatedRuns>> {
+    user_guard(user.has_permission(Permission::InvalidateRuns))?;
+    let reason = check_reason(&body.reason)?;
+    let run_id = path.into_inner();
+    let run: Option<(u64,)> = sqlx::query_as("SELECT r.run_id FROM runs r WHERE r.run_id = ?")
+        .bind(run_id)
+        .fetch_optional(db.get_ref()).await?;
+    if run.is_none() {
+        return Err(ApiError::UnknownRun);
+    }
+    let runs = invalidate_runs(db.get_ref(), user.id(), &format!("r.run_id = {run_id}"), reason).await?;
+
+    Ok(Json(InvalidatedRuns { runs }))
+}
+
+#[derive(Deserialize)]
+struct InvalidateCourseRuns {
+    map: String,
+    course: u32,
+    /// Every mode when left out.
+    mode: Option<String>,
+    player_id: Option<u64>,
+    /// Only runs set before this, e.g. when a skip was fixed.
+    before: Option<DateTime<Utc>>,
+    reason: String,
+}
+
+/// Invalidates the runs of one player on a course, or everyone's runs on it from before a date.
+#[post("/runs/invalidate")]
+async fn invalidate_course_runs(user: User, body: Json<InvalidateCourseRuns>, db: Data<MySqlPool>) -> ApiResult<Json<InvalidatedRuns>> {
+    user_guard(user.has_permission(Permission::InvalidateRuns))?;
+    let reason = check_reason(&body.reason)?;
+    if body.player_id.is_none() && body.before.is_none() {
+        return Err(ApiError::BadRequest("either player_id or before is required".to_owned()));
+    }
+    let map_id = resolve_map(db.get_ref(), &body.map).await?;
+    let (course_id,): (u32,) = sqlx::query_as("SELECT c.course_id FROM courses c WHERE c.map_id = ? AND c.num = ?")
+        .bind(map_id)
+        .bind(body.course)
+        .fetch_optional(db.get_ref()).await?
+        .ok_or(ApiError::UnknownCourse)?;
+
+    // Typed values only, see get_maptop.
+    let mut filters = format!("f.course_id = {course_id}");
+    if let Some(mode) = &body.mode {
+        filters += &format!(" AND f.mode_id = {}", resolve_mode(db.get_ref(), mode).await?);
+    }
+    let mut conditions = format!("r.filter_id IN (SELECT f.filter_id FROM filters f WHERE {filters})");
+    if let Some(player_id) = body.player_id {
+        conditions += &format!(" AND r.player_id = {player_id}");
+    }
+    if let Some(before) = body.before {
+        conditions += &format!(" AND r.created_at < '{}'", before.format("%Y-%m-%d %H:%M:%S"));
+    }
+    let runs = invalidate_runs(db.get_ref(), user.id(), &conditions, reason).await?;
+
+    Ok(Json(InvalidatedRuns { runs }))
+}
+
+/// Undoes an invalidation, for when it hit the wrong run.
+#[post("/runs/{run_id}/restore")]
+async fn restore_run(user: User, path: Path<u64>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::InvalidateRuns))?;
+    let run_id = path.into_inner();
+    let (filter_id, invalidated): (u32, bool) = sqlx::query_as(r#"
+        SELECT r.filter_id, r.invalidated_at IS NOT NULL
+        FROM runs r
+        WHERE r.run_id = ?
+    "#)
+    .bind(run_id)
+    .fetch_optional(db.get_ref()).await?
+    .ok_or(ApiError::UnknownRun)?;
+    if !invalidated {
+        return Err(ApiError::BadRequest("run is not invalidated".to_owned()));
+    }
+    sqlx::query(r#"
+        UPDATE runs
+        SET invalidated_at = NULL, invalidated_by = NULL, invalidation_reason = NULL
+        WHERE run_id = ?
+    "#)
+    .bind(run_id)
+    .execute(db.get_ref()).await?;
+
+    recompute_filters(db.get_ref(), &[filter_id]).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
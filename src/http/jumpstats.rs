@@ -0,0 +1,212 @@
+use actix_web::{get, post};
+use actix_web::web::{ServiceConfig, Json, Data, Path, Query};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use super::auth_server::Server;
+use super::bans::{is_banned, not_banned};
+use super::error::{ApiError, ApiResult};
+use super::lookup::resolve_mode;
+use super::model::{JumpstatPb, RankedJump, SubmitJumpstatResponse};
+use super::players::find_player;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(submit_jumpstat)
+        .service(get_jumpstat_top)
+        .service(get_player_jumpstats);
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum JumpType {
+    Longjump,
+    Bhop,
+    MultiBhop,
+    WeirdJump,
+    Ladderjump,
+    Ladderhop,
+    Jumpbug,
+    Countjump,
+}
+
+impl JumpType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JumpType::Longjump => "longjump",
+            JumpType::Bhop => "bhop",
+            JumpType::MultiBhop => "multi_bhop",
+            JumpType::WeirdJump => "weird_jump",
+            JumpType::Ladderjump => "ladderjump",
+            JumpType::Ladderhop => "ladderhop",
+            JumpType::Jumpbug => "jumpbug",
+            JumpType::Countjump => "countjump",
+        }
+    }
+}
+
+const JUMPSTAT_COLUMNS: &str = r#"
+    j.jumpstat_id, j.distance, j.strafes, j.sync, j.pre_speed, j.max_speed, j.airtime, j.binded,
+    j.created_at
+"#;
+
+/// Condition selecting the jumps that count for a binded or unbinded leaderboard from the
+/// jumpstats table aliased as `alias`. Binded leaderboards take every jump.
+fn binded_condition(binded: bool, alias: &str) -> String {
+    match binded {
+        true => "1".to_owned(),
+        false => format!("NOT {alias}.binded"),
+    }
+}
+
+#[derive(Deserialize)]
+struct SubmitJumpstat {
+    mode: String,
+    jump_type: JumpType,
+    player_id: u64,
+    player_name: String,
+    distance: f64,
+    strafes: u8,
+    sync: f64,
+    pre_speed: f64,
+    max_speed: f64,
+    /// In ticks.
+    airtime: u32,
+    binded: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[post("/jumpstats")]
+async fn submit_jumpstat(server: Server, jump: Json<SubmitJumpstat>, db: Data<MySqlPool>) -> ApiResult<Json<SubmitJumpstatResponse>> {
+    if !jump.distance.is_finite() || jump.distance <= 0.0 {
+        return Err(ApiError::BadRequest("distance must be positive".to_owned()));
+    }
+    if !(0.0..=100.0).contains(&jump.sync) {
+        return Err(ApiError::BadRequest("sync is a percentage".to_owned()));
+    }
+    let mode_id = resolve_mode(db.get_ref(), &jump.mode).await?;
+    if is_banned(db.get_ref(), jump.player_id).await? {
+        return Err(ApiError::PlayerBanned);
+    }
+    let mut tx = db.begin().await?;
+
+    let (binded_pb, unbinded_pb): (Option<f64>, Option<f64>) = sqlx::query_as(r#"
+        SELECT MAX(j.distance), MAX(CASE WHEN NOT j.binded THEN j.distance END)
+        FROM jumpstats j
+        WHERE j.player_id = ? AND j.mode_id = ? AND j.jump_type = ?
+    "#)
+    .bind(jump.player_id)
+    .bind(mode_id)
+    .bind(jump.jump_type.as_str())
+    .fetch_one(&mut tx).await?;
+
+    sqlx::query(r#"
+        INSERT INTO players (player_id, name)
+        VALUES (?, ?)
+        ON DUPLICATE KEY UPDATE name = VALUES(name)
+    "#)
+    .bind(jump.player_id)
+    .bind(&jump.player_name)
+    .execute(&mut tx).await?;
+
+    let jumpstat_id = sqlx::query(r#"
+        INSERT INTO jumpstats (player_id, mode_id, server_id, jump_type, distance, strafes, sync,
+            pre_speed, max_speed, airtime, binded, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    "#)
+    .bind(jump.player_id)
+    .bind(mode_id)
+    .bind(server.id())
+    .bind(jump.jump_type.as_str())
+    .bind(jump.distance)
+    .bind(jump.strafes)
+    .bind(jump.sync)
+    .bind(jump.pre_speed)
+    .bind(jump.max_speed)
+    .bind(jump.airtime)
+    .bind(jump.binded)
+    .bind(jump.created_at)
+    .execute(&mut tx).await?
+    .last_insert_id();
+
+    tx.commit().await?;
+
+    let improves = |best: Option<f64>| best.is_none_or(|best| jump.distance > best);
+    Ok(Json(SubmitJumpstatResponse {
+        jumpstat_id,
+        binded_pb: improves(binded_pb),
+        unbinded_pb: !jump.binded && improves(unbinded_pb),
+    }))
+}
+
+#[derive(Deserialize)]
+struct GetJumpstatTop {
+    mode: String,
+    jump_type: JumpType,
+    /// Defaults to the unbinded leaderboard.
+    binded: Option<bool>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+const JUMPSTAT_TOP_DEFAULT_LIMIT: u64 = 50;
+const JUMPSTAT_TOP_MAX_LIMIT: u64 = 200;
+
+/// Every player's longest jump of a type, best first.
+#[get("/jumpstats")]
+async fn get_jumpstat_top(query: Query<GetJumpstatTop>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<RankedJump>>> {
+    let mode_id = resolve_mode(db.get_ref(), &query.mode).await?;
+    let binded = binded_condition(query.binded.unwrap_or(false), "j");
+    let not_banned = not_banned("j");
+    let result: Vec<RankedJump> = sqlx::query_as(&format!(r#"
+        SELECT CAST(RANK() OVER (ORDER BY j.distance DESC) AS UNSIGNED) AS `rank`,
+            j.player_id, p.name AS player_name, {JUMPSTAT_COLUMNS}
+        FROM (
+            SELECT j.*,
+                ROW_NUMBER() OVER (PARTITION BY j.player_id ORDER BY j.distance DESC, j.created_at ASC) AS n
+            FROM jumpstats j
+            WHERE j.mode_id = ? AND j.jump_type = ? AND {binded} AND {not_banned}
+        ) j
+        LEFT JOIN players p ON p.player_id = j.player_id
+        WHERE j.n = 1
+        ORDER BY j.distance DESC, j.created_at ASC
+        LIMIT ? OFFSET ?
+    "#))
+    .bind(mode_id)
+    .bind(query.jump_type.as_str())
+    .bind(query.limit.unwrap_or(JUMPSTAT_TOP_DEFAULT_LIMIT).clamp(1, JUMPSTAT_TOP_MAX_LIMIT))
+    .bind(query.offset.unwrap_or(0))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct GetPlayerJumpstats {
+    mode: String,
+    /// Defaults to the unbinded PBs.
+    binded: Option<bool>,
+}
+
+/// The player's longest jump of every type.
+#[get("/players/{steamid}/jumpstats")]
+async fn get_player_jumpstats(path: Path<String>, query: Query<GetPlayerJumpstats>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<JumpstatPb>>> {
+    let (_, player) = find_player(db.get_ref(), &path).await?;
+    let mode_id = resolve_mode(db.get_ref(), &query.mode).await?;
+    let binded = binded_condition(query.binded.unwrap_or(false), "j");
+    let result: Vec<JumpstatPb> = sqlx::query_as(&format!(r#"
+        SELECT j.jump_type, {JUMPSTAT_COLUMNS}
+        FROM (
+            SELECT j.*,
+                ROW_NUMBER() OVER (PARTITION BY j.jump_type ORDER BY j.distance DESC, j.created_at ASC) AS n
+            FROM jumpstats j
+            WHERE j.player_id = ? AND j.mode_id = ? AND {binded}
+        ) j
+        WHERE j.n = 1
+        ORDER BY j.jump_type
+    "#))
+    .bind(player.id)
+    .bind(mode_id)
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
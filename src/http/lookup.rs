@@ -0,0 +1,47 @@
+use sqlx::{FromRow, MySqlPool};
+use super::error::{ApiError, ApiResult};
+
+#[derive(FromRow)]
+struct ResolvedFilter {
+    course_id: Option<u32>,
+    mode_id: Option<u32>,
+    filter_id: Option<u32>,
+}
+
+/// Finds the filter of a map/course/mode combination, telling apart which of them doesn't exist.
+pub async fn resolve_filter(db: &MySqlPool, map: &str, course: u32, mode: &str) -> ApiResult<u32> {
+    // Everything is LEFT JOINed against the map so we can tell which part is wrong.
+    let filter: ResolvedFilter = sqlx::query_as(r#"
+        SELECT c.course_id, m2.mode_id, f.filter_id
+        FROM maps m
+        LEFT JOIN courses c ON c.map_id = m.map_id AND c.num = ?
+        LEFT JOIN modes m2 ON m2.short_name = ?
+        LEFT JOIN filters f ON f.course_id = c.course_id AND f.mode_id = m2.mode_id
+        WHERE m.name = ?
+        LIMIT 1
+    "#)
+    .bind(course)
+    .bind(mode)
+    .bind(map)
+    .fetch_optional(db).await?
+    .ok_or(ApiError::UnknownMap)?;
+    filter.course_id.ok_or(ApiError::UnknownCourse)?;
+    filter.mode_id.ok_or(ApiError::UnknownMode)?;
+    filter.filter_id.ok_or(ApiError::UnknownFilter)
+}
+
+pub async fn resolve_mode(db: &MySqlPool, mode: &str) -> ApiResult<u32> {
+    let mode: (u32,) = sqlx::query_as("SELECT m.mode_id FROM modes m WHERE m.short_name = ?")
+        .bind(mode)
+        .fetch_optional(db).await?
+        .ok_or(ApiError::UnknownMode)?;
+    Ok(mode.0)
+}
+
+pub async fn resolve_map(db: &MySqlPool, map: &str) -> ApiResult<u32> {
+    let map: (u32,) = sqlx::query_as("SELECT m.map_id FROM maps m WHERE m.name = ?")
+        .bind(map)
+        .fetch_optional(db).await?
+        .ok_or(ApiError::UnknownMap)?;
+    Ok(map.0)
+}
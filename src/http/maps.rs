@@ -1,13 +1,23 @@
-use actix_web::get;
-use actix_web::error::Result;
-use actix_web::web::{ServiceConfig, Json, Data, Query};
+use actix_web::{get, post, HttpResponse};
+use actix_web::web::{ServiceConfig, Json, Data, Path, Query};
 use serde::Deserialize;
 use sqlx::mysql::MySqlPool;
-use super::model::Map;
+use sqlx::{MySql, QueryBuilder};
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::invalidation::check_reason;
+use super::lookup::{resolve_map, resolve_mode};
+use super::model::{Map, MapReview, PendingMap};
 
 pub fn config(conf: &mut ServiceConfig) {
     conf.service(get_map)
-        .service(get_maps);
+        .service(get_maps)
+        .service(get_pending_maps)
+        .service(get_map_reviews)
+        .service(submit_map)
+        .service(validate_map)
+        .service(reject_map)
+        .service(unvalidate_map);
 }
 
 #[derive(Deserialize)]
@@ -17,7 +27,8 @@ struct GetMap {
 }
 
 #[get("/get_map")]
-async fn get_map(query: Query<GetMap>, db: Data<MySqlPool>) -> Result<Json<Map>> {
+async fn get_map(query: Query<GetMap>, db: Data<MySqlPool>) -> ApiResult<Json<Map>> {
+    resolve_mode(db.get_ref(), &query.mode).await?;
     let result: Map = sqlx::query_as(r#"
         SELECT m.name, m.created_at,
             CASE WHEN c.num IS NULL 
@@ -46,9 +57,8 @@ async fn get_map(query: Query<GetMap>, db: Data<MySqlPool>) -> Result<Json<Map>>
     "#)
     .bind(&query.mode)
     .bind(&query.map)
-    .fetch_optional(db.get_ref()).await
-    .map_err(|_| actix_web::error::ErrorInternalServerError(""))?
-    .ok_or(actix_web::error::ErrorNotFound(""))?;
+    .fetch_optional(db.get_ref()).await?
+    .ok_or(ApiError::UnknownMap)?;
 
     Ok(Json(result))
 }
@@ -59,7 +69,8 @@ struct GetMaps {
 }
 
 #[get("/get_maps")]
-async fn get_maps(query: Query<GetMaps>, db: Data<MySqlPool>) -> Result<Json<Vec<Map>>> {
+async fn get_maps(query: Query<GetMaps>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Map>>> {
+    resolve_mode(db.get_ref(), &query.mode).await?;
     let result: Vec<Map> = sqlx::query_as(r#"
         SELECT m.name, m.created_at,
             CASE WHEN c.num IS NULL 
@@ -88,8 +99,215 @@ async fn get_maps(query: Query<GetMaps>, db: Data<MySqlPool>) -> Result<Json<Vec
         ORDER BY m.name
     "#)
     .bind(&query.mode)
-    .fetch_all(db.get_ref()).await
-    .map_err(|_| actix_web::error::ErrorInternalServerError(""))?;
+    .fetch_all(db.get_ref()).await?;
 
     Ok(Json(result))
 }
+
+#[derive(Deserialize)]
+struct SubmitMap {
+    name: String,
+    workshop_id: Option<u64>,
+    courses: Vec<u32>,
+    mappers: Vec<u64>,
+}
+
+/// Submits a new, not yet validated map. Anyone logged in can do this, reviewers decide later.
+#[post("/maps")]
+async fn submit_map(user: User, body: Json<SubmitMap>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    let SubmitMap { name, workshop_id, mut courses, mut mappers } = body.into_inner();
+    let valid_name = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return Err(ApiError::BadRequest("map names must be 1 to 64 lowercase letters, digits or underscores".to_owned()));
+    }
+    courses.sort_unstable();
+    courses.dedup();
+    if courses.is_empty() {
+        return Err(ApiError::BadRequest("a map needs at least one course".to_owned()));
+    }
+    mappers.sort_unstable();
+    mappers.dedup();
+
+    let mut tx = db.begin().await?;
+    let exists: Option<(u32,)> = sqlx::query_as("SELECT m.map_id FROM maps m WHERE m.name = ? FOR UPDATE")
+        .bind(&name)
+        .fetch_optional(&mut tx).await?;
+    if exists.is_some() {
+        return Err(ApiError::MapExists);
+    }
+
+    // Mappers are listed by joining players, an unknown id would break every listing of the map.
+    if !mappers.is_empty() {
+        let ids = mappers.iter().map(u64::to_string).collect::<Vec<_>>().join(",");
+        let known: Vec<(u64,)> = sqlx::query_as(&format!("SELECT p.player_id FROM players p WHERE p.player_id IN ({ids})"))
+            .fetch_all(&mut tx).await?;
+        if let Some(unknown) = mappers.iter().find(|id| !known.iter().any(|(k,)| k == *id)) {
+            return Err(ApiError::BadRequest(format!("mapper {unknown} is not a known player")));
+        }
+    }
+
+    // search_tags feeds the FULLTEXT index used by search_maps, which treats '_' as a separator.
+    let map_id = sqlx::query(r#"
+        INSERT INTO maps (name, search_tags, workshop_id, submitted_by, validated)
+        VALUES (?, ?, ?, ?, FALSE)
+    "#)
+    .bind(&name)
+    .bind(name.replace('_', " "))
+    .bind(workshop_id)
+    .bind(user.id())
+    .execute(&mut tx).await?
+    .last_insert_id();
+
+    QueryBuilder::<MySql>::new("INSERT INTO courses (map_id, num) ")
+        .push_values(&courses, |mut b, course| {
+            b.push_bind(map_id).push_bind(*course);
+        })
+        .build()
+        .execute(&mut tx).await?;
+
+    // Every course is playable in every mode, tiers get assigned during review.
+    sqlx::query(r#"
+        INSERT INTO filters (course_id, mode_id)
+        SELECT c.course_id, m2.mode_id
+        FROM courses c
+        CROSS JOIN modes m2
+        WHERE c.map_id = ?
+    "#)
+    .bind(map_id)
+    .execute(&mut tx).await?;
+
+    if !mappers.is_empty() {
+        QueryBuilder::<MySql>::new("INSERT INTO mappers (map_id, player_id) ")
+            .push_values(&mappers, |mut b, player_id| {
+                b.push_bind(map_id).push_bind(*player_id);
+            })
+            .build()
+            .execute(&mut tx).await?;
+    }
+
+    sqlx::query("INSERT INTO map_reviews (map_id, player_id, action) VALUES (?, ?, 'submit')")
+        .bind(map_id)
+        .bind(user.id())
+        .execute(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+#[get("/maps/pending")]
+async fn get_pending_maps(user: User, db: Data<MySqlPool>) -> ApiResult<Json<Vec<PendingMap>>> {
+    user_guard(user.has_permission(Permission::ViewMaps))?;
+    let result: Vec<PendingMap> = sqlx::query_as(r#"
+        SELECT m.name, m.workshop_id, m.submitted_by, m.created_at
+        FROM maps m
+        WHERE NOT m.validated AND m.rejection_reason IS NULL
+        ORDER BY m.created_at ASC
+    "#)
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[get("/maps/{map}/reviews")]
+async fn get_map_reviews(user: User, path: Path<String>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<MapReview>>> {
+    user_guard(user.has_permission(Permission::ViewMaps))?;
+    let map_id = resolve_map(db.get_ref(), &path).await?;
+    let result: Vec<MapReview> = sqlx::query_as(r#"
+        SELECT CAST(mr.action AS CHAR) AS action, mr.player_id, p.name AS player_name, mr.reason, mr.created_at
+        FROM map_reviews mr
+        LEFT JOIN players p ON p.player_id = mr.player_id
+        WHERE mr.map_id = ?
+        ORDER BY mr.created_at ASC, mr.review_id ASC
+    "#)
+    .bind(map_id)
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct ReviewMap {
+    reason: Option<String>,
+}
+
+/// Where a map is in its review. Unvalidating puts a map back in the queue.
+#[derive(Clone, Copy, PartialEq)]
+enum MapState {
+    Pending,
+    Validated,
+    Rejected,
+}
+
+impl MapState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MapState::Pending => "pending",
+            MapState::Validated => "validated",
+            MapState::Rejected => "rejected",
+        }
+    }
+}
+
+/// Moves the map from the `from` state to `to` and records who did it. Rejections keep their
+/// reason on the map. Callers check the user may.
+async fn review_map(db: &MySqlPool, user: &User, map: &str, action: &str, from: MapState, to: MapState, reason: Option<&str>) -> ApiResult<HttpResponse> {
+    let map_id = resolve_map(db, map).await?;
+
+    let mut tx = db.begin().await?;
+    let (current_validated, current_rejection): (bool, Option<String>) = sqlx::query_as(r#"
+        SELECT m.validated, m.rejection_reason
+        FROM maps m
+        WHERE m.map_id = ?
+        FOR UPDATE
+    "#)
+    .bind(map_id)
+    .fetch_one(&mut tx).await?;
+    let current = match (current_validated, current_rejection) {
+        (true, _) => MapState::Validated,
+        (false, Some(_)) => MapState::Rejected,
+        (false, None) => MapState::Pending,
+    };
+    // Anything else would only leave a misleading entry in the review log.
+    if current != from {
+        return Err(ApiError::BadRequest(format!("can't {action} a {} map", current.as_str())));
+    }
+
+    sqlx::query("UPDATE maps SET validated = ?, rejection_reason = ? WHERE map_id = ?")
+        .bind(to == MapState::Validated)
+        .bind(reason.filter(|_| to == MapState::Rejected))
+        .bind(map_id)
+        .execute(&mut tx).await?;
+    sqlx::query("INSERT INTO map_reviews (map_id, player_id, action, reason) VALUES (?, ?, ?, ?)")
+        .bind(map_id)
+        .bind(user.id())
+        .bind(action)
+        .bind(reason)
+        .execute(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/maps/{map}/validate")]
+async fn validate_map(user: User, path: Path<String>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ApproveMaps))?;
+    review_map(db.get_ref(), &user, &path, "validate", MapState::Pending, MapState::Validated, None).await
+}
+
+#[post("/maps/{map}/reject")]
+async fn reject_map(user: User, path: Path<String>, body: Json<ReviewMap>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ApproveMaps))?;
+    let reason = body.reason.as_deref()
+        .ok_or_else(|| ApiError::BadRequest("rejecting a map needs a reason".to_owned()))
+        .and_then(check_reason)?;
+    review_map(db.get_ref(), &user, &path, "reject", MapState::Pending, MapState::Rejected, Some(reason)).await
+}
+
+#[post("/maps/{map}/unvalidate")]
+async fn unvalidate_map(user: User, path: Path<String>, body: Json<ReviewMap>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ApproveMaps))?;
+    let reason = body.reason.as_deref().map(check_reason).transpose()?;
+    review_map(db.get_ref(), &user, &path, "unvalidate", MapState::Validated, MapState::Pending, reason).await
+}
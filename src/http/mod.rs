@@ -1,29 +1,133 @@
 use actix_cors::Cors;
-use actix_web::web::Data;
+use actix_web::web::{Data, JsonConfig, PathConfig, QueryConfig, ServiceConfig};
 use actix_web::{HttpServer, App};
+use anyhow::Context;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use sqlx::MySqlPool;
+use crate::config::{HttpConfig, TlsConfig};
+use self::auth_user::LocalData;
+use self::cors::CorsPolicy;
+use self::error::ApiError;
+use self::points::RankingsRecompute;
+use self::replay_storage::{replay_storage, ReplayStorage};
+use self::validation::RunValidation;
 
 mod auth_server;
 mod auth_user;
+mod bans;
+mod cors;
+mod error;
+mod invalidation;
+mod jumpstats;
+mod lookup;
 mod model;
 mod runs;
 mod maps;
 mod modes;
+mod players;
+mod points;
+mod recent;
+mod replay_storage;
+mod replays;
+mod roles;
 mod search;
+mod servers;
+mod steamid;
+mod tiers;
+mod token;
+mod validation;
 
-pub async fn serve(db: MySqlPool) -> anyhow::Result<()> {
-    HttpServer::new(move || {
-        App::new()
-            .wrap(Cors::permissive())
-            .app_data(Data::new(db.clone()))
+/// What the routes share besides the database, built once up front so a bad setting fails
+/// startup instead of every worker.
+#[derive(Clone)]
+pub struct AppState {
+    db: MySqlPool,
+    auth: Data<LocalData>,
+    storage: Data<dyn ReplayStorage>,
+    validation: Data<RunValidation>,
+    rankings: Data<RankingsRecompute>,
+    cors: CorsPolicy,
+}
+
+impl AppState {
+    pub fn new(db: MySqlPool, config: &HttpConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            db,
+            auth: Data::new(LocalData::new(config)?),
+            storage: Data::from(replay_storage(&config.replay_dir)),
+            validation: Data::new(RunValidation {
+                min_ticks: config.run_min_ticks,
+                max_record_improvement: config.run_max_record_improvement,
+            }),
+            rankings: Data::new(RankingsRecompute::default()),
+            cors: CorsPolicy::new(config)?,
+        })
+    }
+
+    /// The CORS middleware to wrap the app in.
+    pub fn cors(&self) -> Cors {
+        self.cors.cors()
+    }
+
+    /// Registers every route along with the data they need.
+    pub fn configure(&self, conf: &mut ServiceConfig) {
+        conf.app_data(Data::new(self.db.clone()))
+            .app_data(self.auth.clone())
+            .app_data(self.storage.clone())
+            .app_data(self.validation.clone())
+            .app_data(self.rankings.clone())
+            .app_data(QueryConfig::default().error_handler(|e, _| ApiError::BadRequest(e.to_string()).into()))
+            .app_data(JsonConfig::default().error_handler(|e, _| ApiError::BadRequest(e.to_string()).into()))
+            .app_data(PathConfig::default().error_handler(|e, _| ApiError::BadRequest(e.to_string()).into()))
             .configure(auth_user::config)
+            .configure(bans::config)
+            .configure(invalidation::config)
+            .configure(jumpstats::config)
             .configure(runs::config)
             .configure(maps::config)
             .configure(modes::config)
+            .configure(players::config)
+            .configure(points::config)
+            .configure(recent::config)
+            .configure(replays::config)
+            .configure(roles::config)
             .configure(search::config)
+            .configure(servers::config)
+            .configure(tiers::config)
+            .configure(validation::config);
+    }
+}
+
+pub async fn serve(db: MySqlPool, config: HttpConfig) -> anyhow::Result<()> {
+    let state = AppState::new(db, &config)?;
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(state.cors())
+            .configure(|conf| state.configure(conf))
     })
-    .bind(("0.0.0.0", 9000))?
-    .run().await?;
+    .client_request_timeout(config.request_timeout)
+    .keep_alive(config.keep_alive)
+    .shutdown_timeout(config.shutdown_timeout.as_secs());
+    let server = match config.workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+    let server = match &config.tls {
+        Some(tls) => server.bind_openssl(config.bind_address, ssl_acceptor(tls)?),
+        None => server.bind(config.bind_address),
+    }
+    .with_context(|| format!("could not bind to {}", config.bind_address))?;
+
+    server.run().await?;
 
     Ok(())
 }
+
+fn ssl_acceptor(tls: &TlsConfig) -> anyhow::Result<openssl::ssl::SslAcceptorBuilder> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key_file(&tls.private_key, SslFiletype::PEM)
+        .with_context(|| format!("could not load TLS private key {}", tls.private_key.display()))?;
+    builder.set_certificate_chain_file(&tls.certificate)
+        .with_context(|| format!("could not load TLS certificate {}", tls.certificate.display()))?;
+    Ok(builder)
+}
@@ -2,15 +2,53 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{Row, FromRow};
 use sqlx::mysql::MySqlRow;
+use super::auth_user::Permission;
+use super::steamid::SteamIdFormats;
 
-#[derive(Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum RunKind {
     NUB,
     PRO,
 }
 
+impl RunKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunKind::NUB => "NUB",
+            RunKind::PRO => "PRO",
+        }
+    }
+
+    /// The runs index matching this kind's leaderboards.
+    pub fn index(&self) -> &'static str {
+        match self {
+            RunKind::NUB => "idx_runs__filterid_playerid_ticks_createdat",
+            RunKind::PRO => "idx_runs__filterid_tps_playerid_ticks_createdat",
+        }
+    }
+
+    /// The `filters` column holding this kind's tier.
+    pub fn tier_column(&self) -> &'static str {
+        match self {
+            RunKind::NUB => "nub_tier",
+            RunKind::PRO => "pro_tier",
+        }
+    }
+
+    /// Condition selecting the runs of this kind from the runs table aliased as `alias`.
+    pub fn teleports(&self, alias: &str) -> String {
+        match self {
+            RunKind::NUB => "1".to_owned(),
+            RunKind::PRO => format!("{alias}.teleports = 0"),
+        }
+    }
+}
+
 #[derive(Serialize, FromRow)]
 pub struct MapRun {
+    rank: u64,
+    run_id: u64,
     player_id: u64,
     player_name: Option<String>,
     ticks: u32,
@@ -18,6 +56,97 @@ pub struct MapRun {
     created_at: DateTime<Utc>,
 }
 
+#[derive(FromRow)]
+pub struct RecordRun {
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub ticks: u32,
+    pub teleports: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct Record {
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub ticks: u32,
+    pub teleports: u32,
+    pub created_at: DateTime<Utc>,
+    /// When the record was beaten, `None` for the current record.
+    pub beaten_at: Option<DateTime<Utc>>,
+    /// How long the record stood (or has stood so far), in seconds.
+    pub held_for: i64,
+}
+
+#[derive(Serialize)]
+pub struct RecentRun {
+    pub run_id: u64,
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub map: String,
+    pub course: u32,
+    pub mode: String,
+    pub ticks: u32,
+    pub teleports: u32,
+    pub created_at: DateTime<Utc>,
+    /// The time this run beat, if there was one.
+    pub previous: Option<PreviousRun>,
+    pub improvement_ticks: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct PreviousRun {
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub ticks: u32,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct RankedPlayer {
+    pub rank: u64,
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub points: u32,
+    pub courses: u32,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct CoursePoints {
+    pub map: String,
+    pub course: u32,
+    pub tier: u32,
+    pub placement: u32,
+    pub ticks: u32,
+    pub points: u32,
+}
+
+#[derive(Serialize)]
+pub struct PointsBreakdown {
+    pub player_id: u64,
+    pub points: u32,
+    pub courses: Vec<CoursePoints>,
+}
+
+#[derive(Serialize)]
+pub struct RecomputeStatus {
+    pub running: bool,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Ban {
+    pub id: u32,
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub ban_type: String,
+    pub reason: String,
+    pub notes: Option<String>,
+    pub banned_by: Option<u64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub active: bool,
+}
+
 #[derive(Serialize, FromRow)]
 pub struct Run {
     pub ticks: u32,
@@ -27,8 +156,83 @@ pub struct Run {
 
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Player {
-    id: u64,
-    name: String,
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct PlayerProfile {
+    pub id: u64,
+    pub name: String,
+    pub steamid: SteamIdFormats,
+    pub banned: bool,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub total_runs: i64,
+    pub stats: Vec<PlayerModeStats>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct PersonalBest {
+    map: String,
+    course: u32,
+    mode: String,
+    tier: Option<u32>,
+    run_id: u64,
+    ticks: u32,
+    teleports: u32,
+    created_at: DateTime<Utc>,
+    rank: u64,
+    record_ticks: u32,
+    /// How far behind the record the PB is, 0 for records.
+    gap_ticks: u32,
+    /// Whether the player also finished the course without teleports.
+    has_pro: bool,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct UnfinishedCourse {
+    map: String,
+    course: u32,
+    tier: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct ComparedRun {
+    pub ticks: u32,
+    pub teleports: u32,
+    pub rank: u64,
+}
+
+#[derive(Serialize)]
+pub struct ComparedCourse {
+    pub map: String,
+    pub course: u32,
+    pub tier: Option<u32>,
+    pub player_a: ComparedRun,
+    pub player_b: ComparedRun,
+    /// Positive when player A is slower.
+    pub delta_ticks: i64,
+}
+
+#[derive(Serialize)]
+pub struct PlayerComparison {
+    pub player_a: Player,
+    pub player_b: Player,
+    pub shared_courses: u32,
+    pub player_a_faster: u32,
+    pub player_b_faster: u32,
+    pub ties: u32,
+    pub courses: Vec<ComparedCourse>,
+}
+
+#[derive(Serialize)]
+pub struct PlayerModeStats {
+    pub mode: String,
+    pub kind: RunKind,
+    pub completed_maps: i64,
+    pub completed_courses: i64,
+    pub records: i64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -61,14 +265,182 @@ impl<'c> FromRow<'c, MySqlRow> for Map {
     }
 }
 
+#[derive(Serialize, FromRow)]
+pub struct PendingMap {
+    pub name: String,
+    pub workshop_id: Option<u64>,
+    pub submitted_by: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct MapReview {
+    pub action: String,
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct RunReview {
+    pub id: u32,
+    pub run_id: u64,
+    pub player_id: u64,
+    pub player_name: Option<String>,
+    pub map: String,
+    pub course: u32,
+    pub mode: String,
+    pub ticks: u32,
+    pub teleports: u32,
+    pub server_id: Option<u32>,
+    pub run_created_at: DateTime<Utc>,
+    pub reason: String,
+    pub status: String,
+    pub reviewed_by: Option<u64>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub notes: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct InvalidatedRuns {
+    /// How many runs were valid before.
+    pub runs: u64,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct TierChange {
+    pub map: String,
+    pub course: u32,
+    pub mode: String,
+    pub kind: String,
+    pub old_tier: Option<u32>,
+    pub new_tier: Option<u32>,
+    pub changed_by: u64,
+    pub changed_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct GameServer {
+    pub id: u32,
+    pub name: String,
+    pub owner_id: Option<u64>,
+    pub owner_name: Option<String>,
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    pub region: Option<String>,
+    pub disabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct LiveServer {
+    pub id: u32,
+    pub name: String,
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    pub region: Option<String>,
+    pub map: Option<String>,
+    pub player_count: Option<u16>,
+    pub max_players: Option<u16>,
+    pub plugin_version: Option<String>,
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    pub online: bool,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Replay {
+    pub run_id: u64,
+    pub size: u32,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct RankedJump {
+    rank: u64,
+    jumpstat_id: u64,
+    player_id: u64,
+    player_name: Option<String>,
+    distance: f64,
+    strafes: u8,
+    sync: f64,
+    pre_speed: f64,
+    max_speed: f64,
+    airtime: u32,
+    binded: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct JumpstatPb {
+    jump_type: String,
+    jumpstat_id: u64,
+    distance: f64,
+    strafes: u8,
+    sync: f64,
+    pre_speed: f64,
+    max_speed: f64,
+    airtime: u32,
+    binded: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct SubmitJumpstatResponse {
+    pub jumpstat_id: u64,
+    /// Binded leaderboards take every jump, unbinded ones only unbinded jumps.
+    pub binded_pb: bool,
+    pub unbinded_pb: bool,
+}
+
+#[derive(Serialize)]
+pub struct ServerToken {
+    pub server_id: u32,
+    /// Only ever shown once, the API keeps nothing but its hash.
+    pub token: String,
+}
+
 #[derive(Serialize, FromRow)]
 pub struct Mode {
     name: String,
     short_name: String,
 }
 
+#[derive(Serialize)]
+pub struct Role {
+    name: String,
+    permissions: Vec<Permission>,
+}
+
+impl<'c> FromRow<'c, MySqlRow> for Role {
+    fn from_row(row: &'c MySqlRow) -> sqlx::Result<Self> {
+        let permissions: Vec<String> = serde_json::from_str(row.try_get("permissions")?)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Ok(Role {
+            name: row.try_get("name")?,
+            // Unknown permissions are left over from removed variants, they grant nothing.
+            permissions: permissions.iter().filter_map(|p| p.parse().ok()).collect(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct SubmitRunResponse {
+    pub run_id: u64,
+    pub nub_pb: bool,
+    pub pro_pb: bool,
+    pub nub_record: bool,
+    pub pro_record: bool,
+    /// The run looked suspicious and waits for an admin to review it.
+    pub flagged: bool,
+}
+
 #[derive(Serialize)]
 pub struct AuthUserResponse {
     pub player_id: u64,
     pub token: String,
+    pub refresh_token: String,
 }
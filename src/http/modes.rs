@@ -1,7 +1,7 @@
 use actix_web::get;
-use actix_web::error::Result;
 use actix_web::web::{ServiceConfig, Json, Data};
 use sqlx::mysql::MySqlPool;
+use super::error::ApiResult;
 use super::model::Mode;
 
 pub fn config(conf: &mut ServiceConfig) {
@@ -9,14 +9,13 @@ pub fn config(conf: &mut ServiceConfig) {
 }
 
 #[get("/get_modes")]
-async fn get_modes(db: Data<MySqlPool>) -> Result<Json<Vec<Mode>>> {
+async fn get_modes(db: Data<MySqlPool>) -> ApiResult<Json<Vec<Mode>>> {
     let result: Vec<Mode> = sqlx::query_as(r#"
         SELECT m.name, m.short_name
         FROM modes m
         ORDER BY m.mode_id
     "#)
-    .fetch_all(db.get_ref()).await
-    .map_err(|_| actix_web::error::ErrorInternalServerError(""))?;
+    .fetch_all(db.get_ref()).await?;
 
     Ok(Json(result))
 }
@@ -0,0 +1,414 @@
+use actix_web::get;
+use actix_web::web::{ServiceConfig, Json, Data, Path, Query};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::{FromRow, MySqlPool};
+use super::bans::{is_banned, not_banned};
+use super::error::{ApiError, ApiResult};
+use super::invalidation::not_invalidated;
+use super::lookup::resolve_mode;
+use super::model::{ComparedCourse, ComparedRun, PersonalBest, Player, PlayerComparison, PlayerModeStats, PlayerProfile, RunKind, UnfinishedCourse};
+use super::steamid::SteamId;
+use super::validation::not_pending;
+
+pub fn config(conf: &mut ServiceConfig) {
+    // Registered ahead of `get_player`, which would otherwise take "compare" for a SteamID.
+    conf.service(compare_players)
+        .service(get_player)
+        .service(get_player_pbs)
+        .service(get_unfinished_courses);
+}
+
+/// Looks up the player named by a `{steamid}` path segment, in any of the SteamID formats.
+pub async fn find_player(db: &MySqlPool, steamid: &str) -> ApiResult<(SteamId, Player)> {
+    let steamid: SteamId = steamid.parse()
+        .map_err(|_| ApiError::BadRequest(format!("{steamid} is not a valid SteamID")))?;
+    let player = fetch_player(db, steamid.account_id()).await?;
+    Ok((steamid, player))
+}
+
+pub async fn fetch_player(db: &MySqlPool, player_id: u64) -> ApiResult<Player> {
+    let player: Player = sqlx::query_as(r#"
+        SELECT p.player_id AS id, p.name
+        FROM players p
+        WHERE p.player_id = ?
+    "#)
+    .bind(player_id)
+    .fetch_optional(db).await?
+    .ok_or(ApiError::UnknownPlayer)?;
+    Ok(player)
+}
+
+#[derive(FromRow)]
+struct RunTotals {
+    total_runs: i64,
+    first_seen: Option<DateTime<Utc>>,
+    last_seen: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+struct ModeStats {
+    mode: String,
+    nub_maps: i64,
+    nub_courses: i64,
+    nub_records: i64,
+    pro_maps: i64,
+    pro_courses: i64,
+    pro_records: i64,
+}
+
+#[get("/players/{steamid}")]
+async fn get_player(path: Path<String>, db: Data<MySqlPool>) -> ApiResult<Json<PlayerProfile>> {
+    let (steamid, player) = find_player(db.get_ref(), &path).await?;
+    let not_invalidated = not_invalidated("r");
+
+    let totals: RunTotals = sqlx::query_as(&format!(r#"
+        SELECT COUNT(*) AS total_runs, MIN(r.created_at) AS first_seen, MAX(r.created_at) AS last_seen
+        FROM runs r
+        WHERE r.player_id = ? AND {not_invalidated}
+    "#))
+    .bind(player.id)
+    .fetch_one(db.get_ref()).await?;
+
+    // b holds the player's best NUB and PRO time on every filter they finished, which is then
+    // compared against the best time anyone has on that filter to count records.
+    let modes: Vec<ModeStats> = sqlx::query_as(&format!(r#"
+        SELECT m2.short_name AS mode,
+            COUNT(DISTINCT c.map_id) AS nub_maps,
+            COUNT(*) AS nub_courses,
+            COUNT(CASE WHEN b.nub_ticks = (
+                SELECT MIN(r.ticks)
+                FROM runs r
+                  USE INDEX(idx_runs__filterid_playerid_ticks_createdat)
+                WHERE r.filter_id = b.filter_id AND {not_invalidated}
+            ) THEN 1 END) AS nub_records,
+            COUNT(DISTINCT CASE WHEN b.pro_ticks IS NOT NULL THEN c.map_id END) AS pro_maps,
+            COUNT(b.pro_ticks) AS pro_courses,
+            COUNT(CASE WHEN b.pro_ticks = (
+                SELECT MIN(r.ticks)
+                FROM runs r
+                  USE INDEX(idx_runs__filterid_tps_playerid_ticks_createdat)
+                WHERE r.filter_id = b.filter_id AND r.teleports = 0 AND {not_invalidated}
+            ) THEN 1 END) AS pro_records
+        FROM (
+            SELECT r.filter_id,
+                MIN(r.ticks) AS nub_ticks,
+                MIN(CASE WHEN r.teleports = 0 THEN r.ticks END) AS pro_ticks
+            FROM runs r
+            WHERE r.player_id = ? AND {not_invalidated}
+            GROUP BY r.filter_id
+        ) b
+        INNER JOIN filters f ON f.filter_id = b.filter_id
+        INNER JOIN courses c ON c.course_id = f.course_id
+        INNER JOIN modes m2 ON m2.mode_id = f.mode_id
+        GROUP BY m2.mode_id
+        ORDER BY m2.mode_id
+    "#))
+    .bind(player.id)
+    .fetch_all(db.get_ref()).await?;
+
+    let stats = modes.into_iter()
+        .flat_map(|m| [
+            PlayerModeStats {
+                mode: m.mode.clone(),
+                kind: RunKind::NUB,
+                completed_maps: m.nub_maps,
+                completed_courses: m.nub_courses,
+                records: m.nub_records,
+            },
+            PlayerModeStats {
+                mode: m.mode,
+                kind: RunKind::PRO,
+                completed_maps: m.pro_maps,
+                completed_courses: m.pro_courses,
+                records: m.pro_records,
+            },
+        ])
+        .collect();
+
+    Ok(Json(PlayerProfile {
+        id: player.id,
+        name: player.name,
+        steamid: steamid.formats(),
+        banned: is_banned(db.get_ref(), player.id).await?,
+        first_seen: totals.first_seen,
+        last_seen: totals.last_seen,
+        total_runs: totals.total_runs,
+        stats,
+    }))
+}
+
+#[derive(Deserialize)]
+struct ComparePlayers {
+    player_a: u64,
+    player_b: u64,
+    mode: String,
+    kind: RunKind,
+}
+
+#[derive(FromRow)]
+struct SharedCourse {
+    map: String,
+    course: u32,
+    tier: Option<u32>,
+    a_ticks: u32,
+    a_teleports: u32,
+    a_rank: u64,
+    b_ticks: u32,
+    b_teleports: u32,
+    b_rank: u64,
+}
+
+/// Every course both players finished, with their PBs side by side.
+#[get("/players/compare")]
+async fn compare_players(query: Query<ComparePlayers>, db: Data<MySqlPool>) -> ApiResult<Json<PlayerComparison>> {
+    if query.player_a == query.player_b {
+        return Err(ApiError::BadRequest("cannot compare a player with themselves".to_owned()));
+    }
+    let player_a = fetch_player(db.get_ref(), query.player_a).await?;
+    let player_b = fetch_player(db.get_ref(), query.player_b).await?;
+    let mode_id = resolve_mode(db.get_ref(), &query.mode).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let best_teleports = query.kind.teleports("r2");
+    let not_banned = not_banned("r");
+    let best_not_pending = not_pending("r2");
+    let not_pending = not_pending("r");
+    let best_not_invalidated = not_invalidated("r2");
+    let not_invalidated = not_invalidated("r");
+    let tier_column = query.kind.tier_column();
+
+    // Same per player MIN(ticks) grouping as the maptop, ranked over everyone but only on the
+    // filters both players have finished. The two players are kept in even if banned, so they
+    // can still be compared.
+    let rows: Vec<SharedCourse> = sqlx::query_as(&format!(r#"
+        WITH shared AS (
+            SELECT r.filter_id
+            FROM runs r
+            INNER JOIN filters f ON f.filter_id = r.filter_id
+            WHERE f.mode_id = ? AND r.player_id IN (?, ?) AND {teleports} AND {not_invalidated} AND {not_pending}
+            GROUP BY r.filter_id
+            HAVING COUNT(DISTINCT r.player_id) = 2
+        ), bests AS (
+            SELECT r.filter_id, r.player_id, MIN(r.ticks) AS ticks,
+                CAST(RANK() OVER (PARTITION BY r.filter_id ORDER BY MIN(r.ticks) ASC) AS UNSIGNED) AS `rank`
+            FROM runs r
+              USE INDEX({index})
+            INNER JOIN shared s ON s.filter_id = r.filter_id
+            WHERE {teleports} AND {not_invalidated} AND {not_pending} AND ({not_banned} OR r.player_id IN (?, ?))
+            GROUP BY r.filter_id, r.player_id
+        )
+        SELECT m.name AS map, c.num AS course, f.{tier_column} AS tier,
+            a.ticks AS a_ticks, a.rank AS a_rank,
+            (
+                SELECT MIN(r2.teleports)
+                FROM runs r2
+                WHERE r2.filter_id = a.filter_id AND r2.player_id = a.player_id
+                    AND r2.ticks = a.ticks AND {best_teleports} AND {best_not_pending} AND {best_not_invalidated}
+            ) AS a_teleports,
+            b.ticks AS b_ticks, b.rank AS b_rank,
+            (
+                SELECT MIN(r2.teleports)
+                FROM runs r2
+                WHERE r2.filter_id = b.filter_id AND r2.player_id = b.player_id
+                    AND r2.ticks = b.ticks AND {best_teleports} AND {best_not_pending} AND {best_not_invalidated}
+            ) AS b_teleports
+        FROM bests a
+        INNER JOIN bests b ON b.filter_id = a.filter_id AND b.player_id = ?
+        INNER JOIN filters f ON f.filter_id = a.filter_id
+        INNER JOIN courses c ON c.course_id = f.course_id
+        INNER JOIN maps m ON m.map_id = c.map_id
+        WHERE a.player_id = ?
+        ORDER BY m.name ASC, c.num ASC
+    "#))
+    .bind(mode_id)
+    .bind(player_a.id)
+    .bind(player_b.id)
+    .bind(player_a.id)
+    .bind(player_b.id)
+    .bind(player_b.id)
+    .bind(player_a.id)
+    .fetch_all(db.get_ref()).await?;
+
+    let courses: Vec<ComparedCourse> = rows.into_iter()
+        .map(|r| ComparedCourse {
+            delta_ticks: r.a_ticks as i64 - r.b_ticks as i64,
+            map: r.map,
+            course: r.course,
+            tier: r.tier,
+            player_a: ComparedRun {
+                ticks: r.a_ticks,
+                teleports: r.a_teleports,
+                rank: r.a_rank,
+            },
+            player_b: ComparedRun {
+                ticks: r.b_ticks,
+                teleports: r.b_teleports,
+                rank: r.b_rank,
+            },
+        })
+        .collect();
+
+    Ok(Json(PlayerComparison {
+        player_a,
+        player_b,
+        shared_courses: courses.len() as u32,
+        player_a_faster: courses.iter().filter(|c| c.delta_ticks < 0).count() as u32,
+        player_b_faster: courses.iter().filter(|c| c.delta_ticks > 0).count() as u32,
+        ties: courses.iter().filter(|c| c.delta_ticks == 0).count() as u32,
+        courses,
+    }))
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Completion {
+    /// Finished without teleports.
+    Pro,
+    /// Only finished with teleports.
+    TpOnly,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum PbSort {
+    /// Newest first.
+    Date,
+    /// Fastest first.
+    Time,
+    /// Best placed first.
+    Rank,
+}
+
+#[derive(Deserialize)]
+struct GetPlayerPbs {
+    mode: Option<String>,
+    kind: RunKind,
+    tier: Option<u32>,
+    completion: Option<Completion>,
+    /// Defaults to date.
+    sort: Option<PbSort>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+const PBS_DEFAULT_LIMIT: u64 = 100;
+const PBS_MAX_LIMIT: u64 = 500;
+
+/// The player's current PB on every course they finished, with where it places them.
+#[get("/players/{steamid}/pbs")]
+async fn get_player_pbs(path: Path<String>, query: Query<GetPlayerPbs>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<PersonalBest>>> {
+    let (_, player) = find_player(db.get_ref(), &path).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let not_banned = not_banned("r");
+    let not_pending = not_pending("r");
+    let not_invalidated = not_invalidated("r");
+    let tier_column = query.kind.tier_column();
+
+    let mut conditions = String::from("1");
+    if let Some(mode) = &query.mode {
+        conditions += &format!(" AND f.mode_id = {}", resolve_mode(db.get_ref(), mode).await?);
+    }
+    if let Some(tier) = query.tier {
+        conditions += &format!(" AND f.{tier_column} = {tier}");
+    }
+    match query.completion {
+        Some(Completion::Pro) => conditions += " AND b.has_pro",
+        Some(Completion::TpOnly) => conditions += " AND NOT b.has_pro",
+        None => {}
+    }
+    let order = match query.sort.unwrap_or(PbSort::Date) {
+        PbSort::Date => "pb.created_at DESC",
+        PbSort::Time => "b.ticks ASC, pb.created_at DESC",
+        PbSort::Rank => "`rank` ASC, pb.created_at DESC",
+    };
+
+    // Ranks are counted like the maptop's: everyone with a faster run places above. MariaDB has
+    // no LATERAL joins, so they're correlated subqueries and the PB run comes from a window.
+    let faster = format!(r#"
+        FROM runs r
+          USE INDEX({index})
+        WHERE r.filter_id = b.filter_id AND r.ticks < b.ticks AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}
+    "#);
+    let record_ticks = format!("COALESCE((SELECT MIN(r.ticks) {faster}), b.ticks)");
+    let result: Vec<PersonalBest> = sqlx::query_as(&format!(r#"
+        SELECT m.name AS map, c.num AS course, m2.short_name AS mode, f.{tier_column} AS tier,
+            pb.run_id, b.ticks, pb.teleports, pb.created_at, b.has_pro,
+            CAST((SELECT COUNT(DISTINCT r.player_id) {faster}) + 1 AS UNSIGNED) AS `rank`,
+            {record_ticks} AS record_ticks,
+            b.ticks - {record_ticks} AS gap_ticks
+        FROM (
+            SELECT r.filter_id,
+                MIN(CASE WHEN {teleports} THEN r.ticks END) AS ticks,
+                MIN(r.teleports) = 0 AS has_pro
+            FROM runs r
+            WHERE r.player_id = ? AND {not_pending} AND {not_invalidated}
+            GROUP BY r.filter_id
+            HAVING MIN(CASE WHEN {teleports} THEN r.ticks END) IS NOT NULL
+        ) b
+        INNER JOIN filters f ON f.filter_id = b.filter_id
+        INNER JOIN courses c ON c.course_id = f.course_id
+        INNER JOIN maps m ON m.map_id = c.map_id
+        INNER JOIN modes m2 ON m2.mode_id = f.mode_id
+        INNER JOIN (
+            SELECT r.filter_id, r.run_id, r.teleports, r.created_at,
+                ROW_NUMBER() OVER (PARTITION BY r.filter_id ORDER BY r.ticks ASC, r.created_at ASC) AS n
+            FROM runs r
+            WHERE r.player_id = ? AND {teleports} AND {not_pending} AND {not_invalidated}
+        ) pb ON pb.filter_id = b.filter_id AND pb.n = 1
+        WHERE {conditions}
+        ORDER BY {order}
+        LIMIT ? OFFSET ?
+    "#))
+    .bind(player.id)
+    .bind(player.id)
+    .bind(query.limit.unwrap_or(PBS_DEFAULT_LIMIT).clamp(1, PBS_MAX_LIMIT))
+    .bind(query.offset.unwrap_or(0))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct GetUnfinished {
+    mode: String,
+    kind: RunKind,
+    tier: Option<u32>,
+}
+
+/// Courses of validated maps the player has yet to finish, easiest first.
+#[get("/players/{steamid}/unfinished")]
+async fn get_unfinished_courses(path: Path<String>, query: Query<GetUnfinished>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<UnfinishedCourse>>> {
+    let (_, player) = find_player(db.get_ref(), &path).await?;
+    let mode_id = resolve_mode(db.get_ref(), &query.mode).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let not_invalidated = not_invalidated("r");
+    let tier_column = query.kind.tier_column();
+
+    let mut conditions = String::new();
+    if let Some(tier) = query.tier {
+        conditions += &format!(" AND f.{tier_column} = {tier}");
+    }
+
+    let result: Vec<UnfinishedCourse> = sqlx::query_as(&format!(r#"
+        SELECT m.name AS map, c.num AS course, f.{tier_column} AS tier
+        FROM filters f
+        INNER JOIN courses c ON c.course_id = f.course_id
+        INNER JOIN maps m ON m.map_id = c.map_id
+        WHERE m.validated AND f.mode_id = ?{conditions}
+            AND NOT EXISTS (
+                SELECT 1
+                FROM runs r
+                  USE INDEX({index})
+                WHERE r.filter_id = f.filter_id AND r.player_id = ? AND {teleports} AND {not_invalidated}
+            )
+        ORDER BY f.{tier_column} IS NULL, f.{tier_column} ASC, m.name ASC, c.num ASC
+    "#))
+    .bind(mode_id)
+    .bind(player.id)
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
@@ -0,0 +1,309 @@
+use actix_web::{get, post, HttpResponse};
+use actix_web::web::{ServiceConfig, Json, Data, Path, Query};
+use serde::Deserialize;
+use sqlx::{FromRow, MySql, MySqlPool, QueryBuilder};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::auth_user::{user_guard, Permission, User};
+use super::bans::not_banned;
+use super::error::{is_deadlock, ApiError, ApiResult};
+use super::invalidation::not_invalidated;
+use super::lookup::resolve_mode;
+use super::model::{CoursePoints, PointsBreakdown, RankedPlayer, RecomputeStatus, RunKind};
+use super::players::find_player;
+use super::validation::not_pending;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(get_rankings)
+        .service(get_player_points)
+        .service(recompute_rankings)
+        .service(get_recompute_status);
+}
+
+/// Points for a course scale with its tier: a tier 1 course is worth up to 1000, every tier
+/// above adds 500. Half of that is awarded for the time relative to the record and half for the
+/// placement, which falls off with the square root of the rank so every finisher gets something.
+fn course_points(tier: u32, placement: u32, ticks: u32, record_ticks: u32) -> u32 {
+    let max = 1000.0 + 500.0 * tier.saturating_sub(1) as f64;
+    let time_share = record_ticks as f64 / ticks.max(1) as f64;
+    let placement_share = 1.0 / (placement.max(1) as f64).sqrt();
+    (max * (0.5 * time_share + 0.5 * placement_share)).round() as u32
+}
+
+#[derive(FromRow)]
+struct FilterTiers {
+    mode_id: u32,
+    nub_tier: Option<u32>,
+    pro_tier: Option<u32>,
+}
+
+#[derive(FromRow)]
+struct Best {
+    player_id: u64,
+    ticks: u32,
+}
+
+/// Rewrites the points of one filter's leaderboards and the totals of everyone on them.
+/// Needs to run whenever a PB on the filter improves, or its tier changes.
+pub async fn recompute_filter(db: &MySqlPool, filter_id: u32) -> ApiResult<()> {
+    let mut deadlocks = 0;
+    loop {
+        match try_recompute_filter(db, filter_id).await {
+            Err(ApiError::Database(e)) if is_deadlock(&e) && deadlocks < MAX_DEADLOCK_RETRIES => deadlocks += 1,
+            result => return result,
+        }
+    }
+}
+
+const MAX_DEADLOCK_RETRIES: u32 = 3;
+
+async fn try_recompute_filter(db: &MySqlPool, filter_id: u32) -> ApiResult<()> {
+    // Locking the filter first queues up concurrent recomputes of it, and as nothing is read
+    // before the lock is held, each one sees every run committed before it got its turn.
+    let mut tx = db.begin().await?;
+    let tiers: FilterTiers = sqlx::query_as(r#"
+        SELECT f.mode_id, f.nub_tier, f.pro_tier
+        FROM filters f
+        WHERE f.filter_id = ?
+        FOR UPDATE
+    "#)
+    .bind(filter_id)
+    .fetch_optional(&mut tx).await?
+    .ok_or(ApiError::UnknownFilter)?;
+
+    for (kind, tier) in [(RunKind::NUB, tiers.nub_tier), (RunKind::PRO, tiers.pro_tier)] {
+        let index = kind.index();
+        let teleports = kind.teleports("r");
+        let not_banned = not_banned("r");
+        let not_pending = not_pending("r");
+        let not_invalidated = not_invalidated("r");
+        // Unrated courses give no points, but we still clear whatever they gave before.
+        let bests: Vec<Best> = match tier {
+            Some(_) => sqlx::query_as(&format!(r#"
+                SELECT r.player_id, MIN(r.ticks) AS ticks
+                FROM runs r
+                  USE INDEX({index})
+                WHERE r.filter_id = ? AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}
+                GROUP BY r.player_id
+                ORDER BY ticks ASC
+            "#))
+            .bind(filter_id)
+            .fetch_all(&mut tx).await?,
+            None => Vec::new(),
+        };
+
+        let previous: Vec<(u64, u32)> = sqlx::query_as(r#"
+            SELECT cp.player_id, cp.points
+            FROM course_points cp
+            WHERE cp.filter_id = ? AND cp.kind = ?
+        "#)
+        .bind(filter_id)
+        .bind(kind.as_str())
+        .fetch_all(&mut tx).await?;
+        sqlx::query("DELETE FROM course_points WHERE filter_id = ? AND kind = ?")
+            .bind(filter_id)
+            .bind(kind.as_str())
+            .execute(&mut tx).await?;
+
+        let mut points = Vec::with_capacity(bests.len());
+        if let (Some(tier), Some(record)) = (tier, bests.first()) {
+            let record_ticks = record.ticks;
+            let mut placement = 0;
+            for (i, best) in bests.iter().enumerate() {
+                // Ties share a placement, like RANK().
+                if i == 0 || bests[i - 1].ticks != best.ticks {
+                    placement = i as u32 + 1;
+                }
+                points.push((best, placement, course_points(tier, placement, best.ticks, record_ticks)));
+            }
+            for chunk in points.chunks(5000) {
+                QueryBuilder::<MySql>::new("INSERT INTO course_points (filter_id, kind, player_id, mode_id, placement, ticks, points) ")
+                    .push_values(chunk, |mut b, (best, placement, points)| {
+                        b.push_bind(filter_id)
+                            .push_bind(kind.as_str())
+                            .push_bind(best.player_id)
+                            .push_bind(tiers.mode_id)
+                            .push_bind(*placement)
+                            .push_bind(best.ticks)
+                            .push_bind(*points);
+                    })
+                    .build()
+                    .execute(&mut tx).await?;
+            }
+        }
+
+        // The totals move by the difference only, so recomputes of other filters in the mode
+        // touch just the players they share, and always in the order of the primary key so they
+        // can't wait on each other in a circle.
+        let mut deltas: BTreeMap<u64, (i64, i64)> = BTreeMap::new();
+        for (player_id, old) in previous {
+            let delta = deltas.entry(player_id).or_default();
+            delta.0 -= i64::from(old);
+            delta.1 -= 1;
+        }
+        for (best, _, new) in &points {
+            let delta = deltas.entry(best.player_id).or_default();
+            delta.0 += i64::from(*new);
+            delta.1 += 1;
+        }
+        let deltas: Vec<(u64, (i64, i64))> = deltas.into_iter().filter(|(_, delta)| *delta != (0, 0)).collect();
+        for chunk in deltas.chunks(5000) {
+            QueryBuilder::<MySql>::new("INSERT INTO player_points (mode_id, kind, player_id, points, courses) ")
+                .push_values(chunk, |mut b, (player_id, _)| {
+                    b.push_bind(tiers.mode_id)
+                        .push_bind(kind.as_str())
+                        .push_bind(*player_id)
+                        .push_bind(0)
+                        .push_bind(0);
+                })
+                .push(" ON DUPLICATE KEY UPDATE points = points")
+                .build()
+                .execute(&mut tx).await?;
+
+            // Typed values again, formatted in for the same reason as everywhere else.
+            let ids = chunk.iter().map(|(id, _)| id.to_string()).collect::<Vec<_>>().join(",");
+            let points = chunk.iter().map(|(id, (points, _))| format!(" WHEN {id} THEN {points}")).collect::<String>();
+            let courses = chunk.iter().map(|(id, (_, courses))| format!(" WHEN {id} THEN {courses}")).collect::<String>();
+            sqlx::query(&format!(r#"
+                UPDATE player_points
+                SET points = CAST(points AS SIGNED) + CASE player_id{points} END,
+                    courses = CAST(courses AS SIGNED) + CASE player_id{courses} END
+                WHERE mode_id = ? AND kind = ? AND player_id IN ({ids})
+                ORDER BY player_id
+            "#))
+            .bind(tiers.mode_id)
+            .bind(kind.as_str())
+            .execute(&mut tx).await?;
+            sqlx::query(&format!(r#"
+                DELETE FROM player_points
+                WHERE mode_id = ? AND kind = ? AND player_id IN ({ids}) AND courses = 0
+            "#))
+            .bind(tiers.mode_id)
+            .bind(kind.as_str())
+            .execute(&mut tx).await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Recomputes every filter the player has finished, for when they drop out of or come back
+/// to the leaderboards.
+pub async fn recompute_player(db: &MySqlPool, player_id: u64) -> ApiResult<()> {
+    let filters: Vec<(u32,)> = sqlx::query_as("SELECT DISTINCT r.filter_id FROM runs r WHERE r.player_id = ?")
+        .bind(player_id)
+        .fetch_all(db).await?;
+    for (filter_id,) in filters {
+        recompute_filter(db, filter_id).await?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct GetRankings {
+    mode: String,
+    kind: RunKind,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+const RANKINGS_DEFAULT_LIMIT: u64 = 50;
+const RANKINGS_MAX_LIMIT: u64 = 200;
+
+#[get("/rankings")]
+async fn get_rankings(query: Query<GetRankings>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<RankedPlayer>>> {
+    let mode_id = resolve_mode(db.get_ref(), &query.mode).await?;
+    let result: Vec<RankedPlayer> = sqlx::query_as(r#"
+        SELECT CAST(RANK() OVER (ORDER BY pp.points DESC) AS UNSIGNED) AS `rank`,
+            pp.player_id, p.name AS player_name, pp.points, pp.courses
+        FROM player_points pp
+        LEFT JOIN players p ON p.player_id = pp.player_id
+        WHERE pp.mode_id = ? AND pp.kind = ?
+        ORDER BY pp.points DESC, pp.player_id ASC
+        LIMIT ? OFFSET ?
+    "#)
+    .bind(mode_id)
+    .bind(query.kind.as_str())
+    .bind(query.limit.unwrap_or(RANKINGS_DEFAULT_LIMIT).clamp(1, RANKINGS_MAX_LIMIT))
+    .bind(query.offset.unwrap_or(0))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct GetPlayerPoints {
+    mode: String,
+    kind: RunKind,
+}
+
+#[get("/players/{steamid}/points")]
+async fn get_player_points(path: Path<String>, query: Query<GetPlayerPoints>, db: Data<MySqlPool>) -> ApiResult<Json<PointsBreakdown>> {
+    let (_, player) = find_player(db.get_ref(), &path).await?;
+    let mode_id = resolve_mode(db.get_ref(), &query.mode).await?;
+    let tier_column = query.kind.tier_column();
+    let courses: Vec<CoursePoints> = sqlx::query_as(&format!(r#"
+        SELECT m.name AS map, c.num AS course, f.{tier_column} AS tier, cp.placement, cp.ticks, cp.points
+        FROM course_points cp
+        INNER JOIN filters f ON f.filter_id = cp.filter_id
+        INNER JOIN courses c ON c.course_id = f.course_id
+        INNER JOIN maps m ON m.map_id = c.map_id
+        WHERE cp.player_id = ? AND cp.mode_id = ? AND cp.kind = ?
+        ORDER BY cp.points DESC
+    "#))
+    .bind(player.id)
+    .bind(mode_id)
+    .bind(query.kind.as_str())
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(PointsBreakdown {
+        player_id: player.id,
+        points: courses.iter().map(|c| c.points).sum(),
+        courses,
+    }))
+}
+
+/// Keeps full recomputes from overlapping, they would only redo each other's work.
+#[derive(Default)]
+pub struct RankingsRecompute {
+    running: AtomicBool,
+}
+
+/// Rebuilds every filter's points from scratch, for bootstrapping or after changing the formula.
+/// That takes far longer than a request may, so it carries on in the background.
+#[post("/rankings/recompute")]
+async fn recompute_rankings(user: User, db: Data<MySqlPool>, recompute: Data<RankingsRecompute>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ManageRankings))?;
+    if recompute.running.swap(true, Ordering::AcqRel) {
+        return Err(ApiError::RecomputeInProgress);
+    }
+
+    let db = db.get_ref().clone();
+    let recompute = recompute.into_inner();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = recompute_all(&db).await {
+            log::error!("failed to recompute rankings: {e:?}");
+        }
+        recompute.running.store(false, Ordering::Release);
+    });
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+async fn recompute_all(db: &MySqlPool) -> ApiResult<()> {
+    let filters: Vec<(u32,)> = sqlx::query_as("SELECT f.filter_id FROM filters f")
+        .fetch_all(db).await?;
+    for (filter_id,) in filters {
+        recompute_filter(db, filter_id).await?;
+    }
+    Ok(())
+}
+
+#[get("/rankings/recompute")]
+async fn get_recompute_status(user: User, recompute: Data<RankingsRecompute>) -> ApiResult<Json<RecomputeStatus>> {
+    user_guard(user.has_permission(Permission::ManageRankings))?;
+    Ok(Json(RecomputeStatus {
+        running: recompute.running.load(Ordering::Acquire),
+    }))
+}
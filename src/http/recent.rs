@@ -0,0 +1,173 @@
+use actix_web::get;
+use actix_web::web::{ServiceConfig, Json, Data, Query};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use sqlx::{FromRow, MySqlPool};
+use super::bans::not_banned;
+use super::error::{ApiError, ApiResult};
+use super::invalidation::not_invalidated;
+use super::lookup::{resolve_map, resolve_mode};
+use super::model::{PreviousRun, RecentRun, RunKind};
+use super::validation::not_pending;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(get_recent_records)
+        .service(get_recent_runs);
+}
+
+#[derive(Deserialize)]
+struct GetRecent {
+    mode: Option<String>,
+    /// Defaults to NUB.
+    kind: Option<RunKind>,
+    map: Option<String>,
+    tier: Option<u32>,
+    player_id: Option<u64>,
+    limit: Option<u32>,
+    /// Pages through the feed: the `created_at` and `run_id` of the last run of the previous
+    /// page. Defaults to now.
+    before: Option<DateTime<Utc>>,
+    before_run_id: Option<u64>,
+}
+
+const RECENT_DEFAULT_LIMIT: u32 = 20;
+const RECENT_MAX_LIMIT: u32 = 100;
+/// How far back from `before` a page looks. Every candidate is compared against the runs before
+/// it, so this keeps a page from walking the whole history when nothing matches.
+const RECENT_WINDOW_DAYS: i64 = 30;
+
+#[derive(FromRow)]
+struct RecentRow {
+    run_id: u64,
+    player_id: u64,
+    player_name: Option<String>,
+    map: String,
+    course: u32,
+    mode: String,
+    ticks: u32,
+    teleports: u32,
+    created_at: DateTime<Utc>,
+    previous_player_id: Option<u64>,
+    previous_player_name: Option<String>,
+    previous_ticks: Option<u32>,
+}
+
+impl From<RecentRow> for RecentRun {
+    fn from(row: RecentRow) -> Self {
+        let previous = match (row.previous_player_id, row.previous_ticks) {
+            (Some(player_id), Some(ticks)) => Some(PreviousRun {
+                player_id,
+                player_name: row.previous_player_name,
+                ticks,
+            }),
+            _ => None,
+        };
+        RecentRun {
+            improvement_ticks: previous.as_ref().map(|p| p.ticks - row.ticks),
+            previous,
+            run_id: row.run_id,
+            player_id: row.player_id,
+            player_name: row.player_name,
+            map: row.map,
+            course: row.course,
+            mode: row.mode,
+            ticks: row.ticks,
+            teleports: row.teleports,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Newest runs that beat the best time before them, either of anyone (records) or of the same
+/// player (PBs).
+async fn fetch_recent(db: &MySqlPool, query: &GetRecent, records: bool) -> ApiResult<Vec<RecentRun>> {
+    let kind = query.kind.unwrap_or(RunKind::NUB);
+    let index = kind.index();
+    let teleports = kind.teleports("r");
+    let previous_teleports = kind.teleports("r2");
+    let previous_not_banned = not_banned("r2");
+    let previous_not_pending = not_pending("r2");
+    let not_banned = not_banned("r");
+    let not_pending = not_pending("r");
+    let previous_not_invalidated = not_invalidated("r2");
+    let not_invalidated = not_invalidated("r");
+    let tier_column = kind.tier_column();
+
+    // Names are resolved up front so everything left to filter on is a plain number.
+    let mut conditions = String::new();
+    if let Some(mode) = &query.mode {
+        conditions += &format!(" AND f.mode_id = {}", resolve_mode(db, mode).await?);
+    }
+    if let Some(map) = &query.map {
+        conditions += &format!(" AND c.map_id = {}", resolve_map(db, map).await?);
+    }
+    if let Some(tier) = query.tier {
+        conditions += &format!(" AND f.{tier_column} = {tier}");
+    }
+    if let Some(player_id) = query.player_id {
+        conditions += &format!(" AND r.player_id = {player_id}");
+    }
+    let same_player = if records { "" } else { "AND r2.player_id = r.player_id" };
+
+    // Pages are keyed on (created_at, run_id), the order they're sorted in, and only reach back
+    // so far from where they start.
+    let before = query.before.unwrap_or_else(Utc::now);
+    let window_start = before.checked_sub_signed(Duration::days(RECENT_WINDOW_DAYS))
+        .ok_or_else(|| ApiError::BadRequest("before is out of range".to_owned()))?;
+    conditions += &format!(" AND r.created_at >= '{}'", window_start.format("%Y-%m-%d %H:%M:%S"));
+    let before = before.format("%Y-%m-%d %H:%M:%S");
+    match query.before_run_id {
+        Some(run_id) => conditions += &format!(
+            " AND (r.created_at < '{before}' OR (r.created_at = '{before}' AND r.run_id < {run_id}))"
+        ),
+        None => conditions += &format!(" AND r.created_at < '{before}'"),
+    }
+
+    // MariaDB has no LATERAL joins, the best run before each candidate is looked up with
+    // correlated subqueries instead.
+    let previous = format!(r#"
+        FROM runs r2
+          USE INDEX({index})
+        WHERE r2.filter_id = r.filter_id
+            AND r2.created_at < r.created_at
+            AND {previous_teleports}
+            AND {previous_not_banned}
+            AND {previous_not_pending}
+            AND {previous_not_invalidated}
+            {same_player}
+    "#);
+    let rows: Vec<RecentRow> = sqlx::query_as(&format!(r#"
+        SELECT x.*, pp.name AS previous_player_name
+        FROM (
+            SELECT r.run_id, r.player_id, p.name AS player_name, m.name AS map, c.num AS course,
+                m2.short_name AS mode, r.ticks, r.teleports, r.created_at,
+                (SELECT r2.player_id {previous} ORDER BY r2.ticks ASC, r2.created_at ASC LIMIT 1) AS previous_player_id,
+                (SELECT MIN(r2.ticks) {previous}) AS previous_ticks
+            FROM runs r
+            INNER JOIN filters f ON f.filter_id = r.filter_id
+            INNER JOIN courses c ON c.course_id = f.course_id
+            INNER JOIN maps m ON m.map_id = c.map_id
+            INNER JOIN modes m2 ON m2.mode_id = f.mode_id
+            INNER JOIN players p ON p.player_id = r.player_id
+            WHERE {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}{conditions}
+        ) x
+        LEFT JOIN players pp ON pp.player_id = x.previous_player_id
+        WHERE x.previous_ticks IS NULL OR x.ticks < x.previous_ticks
+        ORDER BY x.created_at DESC, x.run_id DESC
+        LIMIT ?
+    "#))
+    .bind(query.limit.unwrap_or(RECENT_DEFAULT_LIMIT).clamp(1, RECENT_MAX_LIMIT))
+    .fetch_all(db).await?;
+
+    Ok(rows.into_iter().map(RecentRun::from).collect())
+}
+
+#[get("/records/recent")]
+async fn get_recent_records(query: Query<GetRecent>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<RecentRun>>> {
+    Ok(Json(fetch_recent(db.get_ref(), &query, true).await?))
+}
+
+#[get("/runs/recent")]
+async fn get_recent_runs(query: Query<GetRecent>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<RecentRun>>> {
+    Ok(Json(fetch_recent(db.get_ref(), &query, false).await?))
+}
@@ -0,0 +1,63 @@
+use actix_web::web::{self, Bytes};
+use futures::future::BoxFuture;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where replay files live. Keys are generated by the API and are plain file names, so any
+/// backend that stores blobs by name (a directory, an S3 bucket) can implement this.
+pub trait ReplayStorage: Send + Sync {
+    fn put(&self, key: &str, data: Bytes) -> BoxFuture<'static, io::Result<()>>;
+    fn get(&self, key: &str) -> BoxFuture<'static, io::Result<Option<Bytes>>>;
+    fn delete(&self, key: &str) -> BoxFuture<'static, io::Result<()>>;
+}
+
+/// Picks the storage backend. Only the local filesystem exists for now, rooted at `replay_dir`.
+pub fn replay_storage(replay_dir: &Path) -> Arc<dyn ReplayStorage> {
+    Arc::new(LocalReplayStorage { root: replay_dir.to_owned() })
+}
+
+pub struct LocalReplayStorage {
+    root: PathBuf,
+}
+
+fn blocking_error(e: actix_web::error::BlockingError) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
+impl ReplayStorage for LocalReplayStorage {
+    fn put(&self, key: &str, data: Bytes) -> BoxFuture<'static, io::Result<()>> {
+        let root = self.root.clone();
+        let path = self.root.join(key);
+        Box::pin(async move {
+            web::block(move || {
+                std::fs::create_dir_all(&root)?;
+                // Written next to the final path first so readers never see half a file.
+                let tmp = path.with_extension("tmp");
+                std::fs::write(&tmp, &data)?;
+                std::fs::rename(&tmp, &path)
+            }).await.map_err(blocking_error)?
+        })
+    }
+
+    fn get(&self, key: &str) -> BoxFuture<'static, io::Result<Option<Bytes>>> {
+        let path = self.root.join(key);
+        Box::pin(async move {
+            web::block(move || match std::fs::read(&path) {
+                Ok(data) => Ok(Some(Bytes::from(data))),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e),
+            }).await.map_err(blocking_error)?
+        })
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'static, io::Result<()>> {
+        let path = self.root.join(key);
+        Box::pin(async move {
+            web::block(move || match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }).await.map_err(blocking_error)?
+        })
+    }
+}
@@ -0,0 +1,201 @@
+use actix_web::{get, web, HttpResponse};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{ServiceConfig, Bytes, Json, Data, Path, PayloadConfig, Query};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, MySqlPool};
+use super::auth_server::Server;
+use super::bans::not_banned;
+use super::error::{ApiError, ApiResult};
+use super::invalidation::not_invalidated;
+use super::lookup::resolve_filter;
+use super::model::{Replay, RunKind};
+use super::replay_storage::ReplayStorage;
+use super::validation::not_pending;
+
+pub fn config(conf: &mut ServiceConfig) {
+    // The raised body limit only applies to the replay's own resource, every other route keeps
+    // the default.
+    conf.service(
+        web::resource("/runs/{run_id}/replay")
+            .app_data(PayloadConfig::new(MAX_REPLAY_SIZE))
+            .route(web::post().to(upload_replay))
+            .route(web::get().to(get_run_replay))
+    )
+    .service(get_record_replay);
+}
+
+const MAX_REPLAY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Replays are only kept while their run is the player's NUB or PRO PB on its course, which
+/// covers the records too. Ties go to the earlier run. Runs waiting for review don't replace
+/// the PB before them yet, its replay is still needed if they get rejected.
+fn is_current_pb(alias: &str) -> String {
+    let pb_not_pending = not_pending("pb");
+    let pb_not_invalidated = not_invalidated("pb");
+    let faster = |pro: &str| format!(r#"
+        NOT EXISTS (
+            SELECT 1
+            FROM runs pb
+            WHERE pb.filter_id = {alias}.filter_id
+                AND pb.player_id = {alias}.player_id
+                AND {pb_not_pending}
+                AND {pb_not_invalidated}
+                {pro}
+                AND (pb.ticks < {alias}.ticks OR (pb.ticks = {alias}.ticks AND pb.run_id < {alias}.run_id))
+        )
+    "#);
+    format!(
+        "({} AND ({} OR ({alias}.teleports = 0 AND {})))",
+        not_invalidated(alias),
+        faster(""),
+        faster("AND pb.teleports = 0"),
+    )
+}
+
+fn storage_error(e: std::io::Error) -> ApiError {
+    ApiError::Internal(format!("replay storage: {e}"))
+}
+
+/// Drops the replays of a player's runs on a filter that are no longer PBs. Files go first, so
+/// a row is only removed once nothing is left behind in the storage.
+pub async fn prune_replays(db: &MySqlPool, storage: &dyn ReplayStorage, filter_id: u32, player_id: u64) -> ApiResult<()> {
+    let is_current_pb = is_current_pb("r");
+    let stale: Vec<(u64, String)> = sqlx::query_as(&format!(r#"
+        SELECT rp.run_id, rp.storage_key
+        FROM replays rp
+        INNER JOIN runs r ON r.run_id = rp.run_id
+        WHERE r.filter_id = ? AND r.player_id = ? AND NOT {is_current_pb}
+    "#))
+    .bind(filter_id)
+    .bind(player_id)
+    .fetch_all(db).await?;
+
+    for (run_id, storage_key) in stale {
+        storage.delete(&storage_key).await.map_err(storage_error)?;
+        sqlx::query("DELETE FROM replays WHERE run_id = ?")
+            .bind(run_id)
+            .execute(db).await?;
+    }
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct UploadTarget {
+    filter_id: u32,
+    player_id: u64,
+    server_id: Option<u32>,
+    is_current_pb: bool,
+}
+
+/// Servers upload the replay of a run they submitted, right after it turned out to be a PB.
+/// Uploading again replaces the previous file.
+async fn upload_replay(server: Server, path: Path<u64>, body: Bytes, db: Data<MySqlPool>, storage: Data<dyn ReplayStorage>) -> ApiResult<Json<Replay>> {
+    let run_id = path.into_inner();
+    let is_current_pb = is_current_pb("r");
+    let run: UploadTarget = sqlx::query_as(&format!(r#"
+        SELECT r.filter_id, r.player_id, r.server_id, {is_current_pb} AS is_current_pb
+        FROM runs r
+        WHERE r.run_id = ?
+    "#))
+    .bind(run_id)
+    .fetch_optional(db.get_ref()).await?
+    .ok_or(ApiError::UnknownRun)?;
+    if run.server_id != Some(server.id()) {
+        return Err(ApiError::Forbidden);
+    }
+    if !run.is_current_pb {
+        return Err(ApiError::BadRequest("replays are only kept for records and current PBs".to_owned()));
+    }
+    if body.is_empty() {
+        return Err(ApiError::BadRequest("replay is empty".to_owned()));
+    }
+
+    let size = body.len() as u32;
+    let sha256 = hex::encode(Sha256::digest(&body));
+    let storage_key = format!("{run_id}.replay");
+    storage.put(&storage_key, body).await.map_err(storage_error)?;
+    sqlx::query(r#"
+        INSERT INTO replays (run_id, size, sha256, storage_key, created_at)
+        VALUES (?, ?, ?, ?, NOW())
+        ON DUPLICATE KEY UPDATE
+            size = VALUES(size),
+            sha256 = VALUES(sha256),
+            storage_key = VALUES(storage_key),
+            created_at = NOW()
+    "#)
+    .bind(run_id)
+    .bind(size)
+    .bind(&sha256)
+    .bind(&storage_key)
+    .execute(db.get_ref()).await?;
+
+    // A PB of the same player may have been submitted since this run was.
+    if let Err(e) = prune_replays(db.get_ref(), storage.get_ref(), run.filter_id, run.player_id).await {
+        log::error!("failed to prune replays of filter {}: {e:?}", run.filter_id);
+    }
+
+    let result: Replay = sqlx::query_as(r#"
+        SELECT rp.run_id, rp.size, rp.sha256, rp.created_at
+        FROM replays rp
+        WHERE rp.run_id = ?
+    "#)
+    .bind(run_id)
+    .fetch_one(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+async fn download_replay(db: &MySqlPool, storage: &dyn ReplayStorage, run_id: u64) -> ApiResult<HttpResponse> {
+    let (storage_key,): (String,) = sqlx::query_as("SELECT rp.storage_key FROM replays rp WHERE rp.run_id = ?")
+        .bind(run_id)
+        .fetch_optional(db).await?
+        .ok_or(ApiError::NotFound("run has no replay"))?;
+    let data = storage.get(&storage_key).await
+        .map_err(storage_error)?
+        .ok_or_else(|| ApiError::Internal(format!("replay {storage_key} is missing from the storage")))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(storage_key)],
+        })
+        .body(data))
+}
+
+/// Run ids come with the maptop, so this covers the top runs of every course.
+async fn get_run_replay(path: Path<u64>, db: Data<MySqlPool>, storage: Data<dyn ReplayStorage>) -> ApiResult<HttpResponse> {
+    download_replay(db.get_ref(), storage.get_ref(), path.into_inner()).await
+}
+
+#[derive(Deserialize)]
+struct GetRecordReplay {
+    map: String,
+    course: u32,
+    mode: String,
+    kind: RunKind,
+}
+
+#[get("/replays/record")]
+async fn get_record_replay(query: Query<GetRecordReplay>, db: Data<MySqlPool>, storage: Data<dyn ReplayStorage>) -> ApiResult<HttpResponse> {
+    let filter_id = resolve_filter(db.get_ref(), &query.map, query.course, &query.mode).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let not_banned = not_banned("r");
+    let not_pending = not_pending("r");
+    let not_invalidated = not_invalidated("r");
+    let (run_id,): (u64,) = sqlx::query_as(&format!(r#"
+        SELECT r.run_id
+        FROM runs r
+          USE INDEX({index})
+        WHERE r.filter_id = ? AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}
+        ORDER BY r.ticks ASC, r.run_id ASC
+        LIMIT 1
+    "#))
+    .bind(filter_id)
+    .fetch_optional(db.get_ref()).await?
+    .ok_or(ApiError::NotFound("course has no record"))?;
+
+    download_replay(db.get_ref(), storage.get_ref(), run_id).await
+}
@@ -0,0 +1,113 @@
+use actix_web::{delete, get, put, HttpResponse};
+use actix_web::web::{ServiceConfig, Json, Data, Path};
+use sqlx::mysql::MySqlPool;
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::model::Role;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(get_roles)
+        .service(get_player_roles)
+        .service(grant_role)
+        .service(revoke_role);
+}
+
+/// Collects the permissions of every role granted to the player.
+pub async fn fetch_permissions(db: &MySqlPool, player_id: u64) -> sqlx::Result<Vec<Permission>> {
+    let permissions: Vec<(String,)> = sqlx::query_as(r#"
+        SELECT DISTINCT rp.permission
+        FROM player_roles pr
+        INNER JOIN role_permissions rp ON rp.role_id = pr.role_id
+        WHERE pr.player_id = ?
+    "#)
+    .bind(player_id)
+    .fetch_all(db).await?;
+
+    Ok(permissions.iter().filter_map(|(p,)| p.parse().ok()).collect())
+}
+
+#[get("/roles")]
+async fn get_roles(user: User, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Role>>> {
+    user_guard(user.has_permission(Permission::ManageRoles))?;
+    let result: Vec<Role> = sqlx::query_as(r#"
+        SELECT r.name,
+            CASE WHEN rp.permission IS NULL
+                THEN JSON_ARRAY()
+                ELSE JSON_ARRAYAGG(rp.permission)
+            END AS permissions
+        FROM roles r
+        LEFT JOIN role_permissions rp ON rp.role_id = r.role_id
+        GROUP BY r.role_id
+        ORDER BY r.name
+    "#)
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[get("/players/{player_id}/roles")]
+async fn get_player_roles(user: User, path: Path<u64>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<String>>> {
+    user_guard(user.has_permission(Permission::ManageRoles))?;
+    let roles: Vec<(String,)> = sqlx::query_as(r#"
+        SELECT r.name
+        FROM player_roles pr
+        INNER JOIN roles r ON r.role_id = pr.role_id
+        WHERE pr.player_id = ?
+        ORDER BY r.name
+    "#)
+    .bind(path.into_inner())
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(roles.into_iter().map(|(name,)| name).collect()))
+}
+
+#[put("/players/{player_id}/roles/{role}")]
+async fn grant_role(user: User, path: Path<(u64, String)>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ManageRoles))?;
+    let (player_id, role) = path.into_inner();
+    let granted = sqlx::query(r#"
+        INSERT IGNORE INTO player_roles (player_id, role_id, granted_by)
+        SELECT ?, r.role_id, ?
+        FROM roles r
+        WHERE r.name = ?
+    "#)
+    .bind(player_id)
+    .bind(user.id())
+    .bind(&role)
+    .execute(db.get_ref()).await?
+    .rows_affected();
+
+    if granted == 0 {
+        // Either the role doesn't exist or the player already has it.
+        let exists: Option<(u32,)> = sqlx::query_as("SELECT r.role_id FROM roles r WHERE r.name = ?")
+            .bind(&role)
+            .fetch_optional(db.get_ref()).await?;
+        if exists.is_none() {
+            return Err(ApiError::UnknownRole);
+        }
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/players/{player_id}/roles/{role}")]
+async fn revoke_role(user: User, path: Path<(u64, String)>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ManageRoles))?;
+    let (player_id, role) = path.into_inner();
+    let revoked = sqlx::query(r#"
+        DELETE pr
+        FROM player_roles pr
+        INNER JOIN roles r ON r.role_id = pr.role_id
+        WHERE pr.player_id = ? AND r.name = ?
+    "#)
+    .bind(player_id)
+    .bind(&role)
+    .execute(db.get_ref()).await?
+    .rows_affected();
+
+    if revoked == 0 {
+        return Err(ApiError::NotFound("player does not have this role"));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,13 +1,25 @@
-use actix_web::error::Result;
-use actix_web::get;
+use actix_web::{get, post};
 use actix_web::web::{ServiceConfig, Json, Query, Data};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use sqlx::MySqlPool;
-use super::model::{MapRun, Run, RunKind};
+use sqlx::{FromRow, MySqlPool};
+use super::auth_server::Server;
+use super::bans::{is_banned, not_banned};
+use super::error::{is_duplicate_key, ApiError, ApiResult};
+use super::invalidation::not_invalidated;
+use super::lookup::resolve_filter;
+use super::model::{MapRun, Record, RecordRun, Run, RunKind, SubmitRunResponse};
+use super::points::recompute_filter;
+use super::replay_storage::ReplayStorage;
+use super::replays::prune_replays;
+use super::steamid::SteamId;
+use super::validation::{flag_run, not_pending, RunValidation};
 
 pub fn config(conf: &mut ServiceConfig) {
     conf.service(get_maptop)
-        .service(get_course_pb_history);
+        .service(get_course_pb_history)
+        .service(get_course_record_history)
+        .service(submit_run);
 }
 
 #[derive(Deserialize)]
@@ -16,41 +28,90 @@ pub struct GetMapTop {
     course: u32,
     mode: String,
     kind: RunKind,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    /// Centers the page on this player's rank instead of using `offset`.
+    player_id: Option<u64>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    server_id: Option<u32>,
 }
 
+const MAPTOP_DEFAULT_LIMIT: u64 = 50;
+const MAPTOP_MAX_LIMIT: u64 = 200;
+
 #[get("/get_maptop")]
-async fn get_maptop(query: Query<GetMapTop>, db: Data<MySqlPool>) -> Result<Json<Vec<MapRun>>> {
-    let (index, teleports) = match query.kind {
-        RunKind::NUB => ("idx_runs__filterid_playerid_ticks_createdat", "1"),
-        RunKind::PRO => ("idx_runs__filterid_tps_playerid_ticks_createdat", "teleports = 0"),
+async fn get_maptop(query: Query<GetMapTop>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<MapRun>>> {
+    let filter_id = resolve_filter(db.get_ref(), &query.map, query.course, &query.mode).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let not_banned = not_banned("r");
+    let not_pending = not_pending("r");
+    let not_invalidated = not_invalidated("r");
+    let limit = query.limit.unwrap_or(MAPTOP_DEFAULT_LIMIT).clamp(1, MAPTOP_MAX_LIMIT);
+
+    // These are typed values, so formatting them into the query is safe, and it saves us
+    // from binding each of them twice.
+    let mut conditions = String::new();
+    if let Some(from) = query.from {
+        conditions += &format!(" AND r.created_at >= '{}'", from.format("%Y-%m-%d %H:%M:%S"));
+    }
+    if let Some(to) = query.to {
+        conditions += &format!(" AND r.created_at < '{}'", to.format("%Y-%m-%d %H:%M:%S"));
+    }
+    if let Some(server_id) = query.server_id {
+        conditions += &format!(" AND r.server_id = {server_id}");
+    }
+
+    let offset = match query.player_id {
+        Some(player_id) => {
+            let (pb,): (Option<u32>,) = sqlx::query_as(&format!(r#"
+                SELECT MIN(r.ticks)
+                FROM runs r
+                USE INDEX({index})
+                WHERE r.filter_id = ? AND r.player_id = ? AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}{conditions}
+            "#))
+            .bind(filter_id)
+            .bind(player_id)
+            .fetch_one(db.get_ref()).await?;
+            let pb = pb.ok_or(ApiError::NotFound("player has no run on this course"))?;
+
+            // Anyone with a single run faster than the player's PB is ranked above them.
+            let (faster,): (i64,) = sqlx::query_as(&format!(r#"
+                SELECT COUNT(DISTINCT r.player_id)
+                FROM runs r
+                USE INDEX({index})
+                WHERE r.filter_id = ? AND r.ticks < ? AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}{conditions}
+            "#))
+            .bind(filter_id)
+            .bind(pb)
+            .fetch_one(db.get_ref()).await?;
+            (faster as u64).saturating_sub(limit / 2)
+        }
+        None => query.offset.unwrap_or(0),
     };
+
+    // Each player's PB is their fastest run, the earliest one when they tied it, picked here rather
+    // than joined back by ticks so resent duplicates and runs left out above can't stand in for it.
     let result: Vec<MapRun> = sqlx::query_as(&format!(r#"
-        SELECT r.player_id, p.name AS player_name, t.ticks, r.teleports, r.created_at
-        FROM runs r
-        USE INDEX({index})
-        INNER JOIN players p ON p.player_id = r.player_id 
-        INNER JOIN (
-            SELECT r.player_id, f.filter_id, MIN(r.ticks) AS ticks
+        SELECT CAST(RANK() OVER (ORDER BY x.ticks ASC) AS UNSIGNED) AS `rank`,
+            x.run_id, x.player_id, p.name AS player_name, x.ticks, x.teleports, x.created_at
+        FROM (
+            SELECT r.run_id, r.player_id, r.ticks, r.teleports, r.created_at,
+                ROW_NUMBER() OVER (PARTITION BY r.player_id ORDER BY r.ticks, r.created_at, r.run_id) AS pb_number
             FROM runs r
             USE INDEX({index})
-            INNER JOIN filters f ON f.filter_id = r.filter_id 
-            INNER JOIN courses c ON c.course_id = f.course_id 
-            INNER JOIN maps m ON m.map_id = c.map_id 
-            INNER JOIN modes m2 ON m2.mode_id = f.mode_id 
-            WHERE m.name = ? AND c.num = ? AND m2.short_name = ? AND {teleports}
-            GROUP BY r.player_id 
-            ORDER BY ticks ASC
-            LIMIT 50
-        ) t ON t.player_id = r.player_id AND t.filter_id = r.filter_id AND t.ticks = r.ticks
-        WHERE {teleports}
-        GROUP BY r.player_id
-        ORDER BY ticks ASC
+            WHERE r.filter_id = ? AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}{conditions}
+        ) x
+        INNER JOIN players p ON p.player_id = x.player_id
+        WHERE x.pb_number = 1
+        ORDER BY `rank` ASC, x.created_at ASC, x.run_id ASC
+        LIMIT ? OFFSET ?
     "#))
-    .bind(&query.map)
-    .bind(query.course)
-    .bind(&query.mode)
-    .fetch_all(db.get_ref()).await
-    .map_err(|_| actix_web::error::ErrorInternalServerError(""))?;
+    .bind(filter_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db.get_ref()).await?;
     Ok(Json(result))
 }
 
@@ -64,11 +125,13 @@ pub struct GetCoursePbHistory {
 }
 
 #[get("/get_course_pb_history")]
-async fn get_course_pb_history(query: Query<GetCoursePbHistory>, db: Data<MySqlPool>) -> Result<Json<Vec<Run>>> {
-    let (index, teleports) = match query.kind {
-        RunKind::NUB => ("idx_runs__filterid_playerid_ticks_createdat", "1"),
-        RunKind::PRO => ("idx_runs__filterid_tps_playerid_ticks_createdat", "teleports = 0"),
-    };
+async fn get_course_pb_history(query: Query<GetCoursePbHistory>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Run>>> {
+    let filter_id = resolve_filter(db.get_ref(), &query.map, query.course, &query.mode).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let outer_teleports = query.kind.teleports("r2");
+    let outer_not_invalidated = not_invalidated("r2");
+    let not_invalidated = not_invalidated("r");
     let runs: Vec<Run> = sqlx::query_as(&format!(r#"
         SELECT x.ticks, x.teleports, x.created_at
         FROM (
@@ -79,32 +142,25 @@ async fn get_course_pb_history(query: Query<GetCoursePbHistory>, db: Data<MySqlP
                 SELECT r.player_id, r.filter_id, r.ticks, MIN(r.created_at) AS created_at
                 FROM runs r
                   USE INDEX({index})
-                INNER JOIN filters f ON f.filter_id = r.filter_id 
-                INNER JOIN courses c ON c.course_id = f.course_id 
-                INNER JOIN maps m ON m.map_id = c.map_id 
-                INNER JOIN modes m2 ON m2.mode_id = f.mode_id
                 WHERE r.player_id = ?
-                    AND m.name = ?
-                    AND c.num = ?
-                    AND m2.short_name = ?
+                    AND r.filter_id = ?
                     AND {teleports}
+                    AND {not_invalidated}
                 GROUP BY r.ticks
             ) p ON p.player_id = r2.player_id
                 AND p.filter_id = r2.filter_id 
                 AND p.ticks = r2.ticks
                 AND p.created_at = r2.created_at
-                AND {teleports}
+                AND {outer_teleports}
+                AND {outer_not_invalidated}
             ORDER BY ticks ASC
             LIMIT 2000
         ) x
         ORDER BY x.created_at ASC
     "#))
     .bind(query.player_id)
-    .bind(&query.map)
-    .bind(query.course)
-    .bind(&query.mode)
-    .fetch_all(db.get_ref()).await
-    .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+    .bind(filter_id)
+    .fetch_all(db.get_ref()).await?;
 
     if runs.is_empty() {
         return Ok(Json(runs));
@@ -122,3 +178,171 @@ async fn get_course_pb_history(query: Query<GetCoursePbHistory>, db: Data<MySqlP
 
     Ok(Json(result))
 }
+
+#[derive(Deserialize)]
+pub struct GetCourseRecordHistory {
+    map: String,
+    course: u32,
+    mode: String,
+    kind: RunKind,
+}
+
+#[get("/get_course_record_history")]
+async fn get_course_record_history(query: Query<GetCourseRecordHistory>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Record>>> {
+    let filter_id = resolve_filter(db.get_ref(), &query.map, query.course, &query.mode).await?;
+    let index = query.kind.index();
+    let teleports = query.kind.teleports("r");
+    let not_banned = not_banned("r");
+    let not_pending = not_pending("r");
+    let not_invalidated = not_invalidated("r");
+    // A run was a record if it beat every run submitted before it.
+    let runs: Vec<RecordRun> = sqlx::query_as(&format!(r#"
+        SELECT x.player_id, p.name AS player_name, x.ticks, x.teleports, x.created_at
+        FROM (
+            SELECT r.player_id, r.ticks, r.teleports, r.created_at,
+                MIN(r.ticks) OVER (
+                    ORDER BY r.created_at ASC, r.run_id ASC
+                    ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+                ) AS previous_best
+            FROM runs r
+              USE INDEX({index})
+            WHERE r.filter_id = ? AND {teleports} AND {not_banned} AND {not_pending} AND {not_invalidated}
+        ) x
+        INNER JOIN players p ON p.player_id = x.player_id
+        WHERE x.previous_best IS NULL OR x.ticks < x.previous_best
+        ORDER BY x.created_at ASC
+    "#))
+    .bind(filter_id)
+    .fetch_all(db.get_ref()).await?;
+
+    let now = Utc::now();
+    let beaten_at: Vec<Option<DateTime<Utc>>> = runs.iter()
+        .skip(1)
+        .map(|r| Some(r.created_at))
+        .chain([None])
+        .collect();
+    let result = runs.into_iter()
+        .zip(beaten_at)
+        .map(|(r, beaten_at)| Record {
+            held_for: (beaten_at.unwrap_or(now) - r.created_at).num_seconds(),
+            beaten_at,
+            player_id: r.player_id,
+            player_name: r.player_name,
+            ticks: r.ticks,
+            teleports: r.teleports,
+            created_at: r.created_at,
+        })
+        .collect();
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+pub struct SubmitRun {
+    map: String,
+    course: u32,
+    mode: String,
+    /// In any of the SteamID formats.
+    player_id: SteamId,
+    player_name: String,
+    ticks: u32,
+    teleports: u32,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct FilterBests {
+    nub_record: Option<u32>,
+    pro_record: Option<u32>,
+    nub_pb: Option<u32>,
+    pro_pb: Option<u32>,
+}
+
+#[post("/runs")]
+async fn submit_run(server: Server, run: Json<SubmitRun>, db: Data<MySqlPool>, storage: Data<dyn ReplayStorage>, validation: Data<RunValidation>) -> ApiResult<Json<SubmitRunResponse>> {
+    let filter_id = resolve_filter(db.get_ref(), &run.map, run.course, &run.mode).await?;
+    let player_id = run.player_id.account_id();
+    if is_banned(db.get_ref(), player_id).await? {
+        return Err(ApiError::PlayerBanned);
+    }
+    validation.check_min_ticks(db.get_ref(), filter_id, run.ticks).await?;
+    let mut tx = db.begin().await?;
+
+    // Banned players and runs waiting for review keep their PBs, but aren't on the leaderboards
+    // to hold records.
+    let not_banned = not_banned("r");
+    let not_pending = not_pending("r");
+    let not_invalidated = not_invalidated("r");
+    let bests: FilterBests = sqlx::query_as(&format!(r#"
+        SELECT MIN(CASE WHEN {not_banned} AND {not_pending} THEN r.ticks END) AS nub_record,
+            MIN(CASE WHEN r.teleports = 0 AND {not_banned} AND {not_pending} THEN r.ticks END) AS pro_record,
+            MIN(CASE WHEN r.player_id = ? THEN r.ticks END) AS nub_pb,
+            MIN(CASE WHEN r.player_id = ? AND r.teleports = 0 THEN r.ticks END) AS pro_pb
+        FROM runs r
+        WHERE r.filter_id = ? AND {not_invalidated}
+    "#))
+    .bind(player_id)
+    .bind(player_id)
+    .bind(filter_id)
+    .fetch_one(&mut tx).await?;
+
+    sqlx::query(r#"
+        INSERT INTO players (player_id, name)
+        VALUES (?, ?)
+        ON DUPLICATE KEY UPDATE name = VALUES(name)
+    "#)
+    .bind(player_id)
+    .bind(&run.player_name)
+    .execute(&mut tx).await?;
+
+    let run_id = sqlx::query(r#"
+        INSERT INTO runs (player_id, filter_id, server_id, ticks, teleports, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+    "#)
+    .bind(player_id)
+    .bind(filter_id)
+    .bind(server.id())
+    .bind(run.ticks)
+    .bind(run.teleports)
+    .bind(run.created_at)
+    .execute(&mut tx).await
+    // Servers resend runs they aren't sure went through, the same player finishing with the same
+    // time at the same moment is one of those.
+    .map_err(|e| if is_duplicate_key(&e) { ApiError::DuplicateRun } else { e.into() })?
+    .last_insert_id();
+
+    let is_pro = run.teleports == 0;
+    let review_reason = validation.review_reason("NUB", run.ticks, bests.nub_record)
+        .or_else(|| validation.review_reason("PRO", run.ticks, bests.pro_record.filter(|_| is_pro)));
+    if let Some(reason) = &review_reason {
+        flag_run(&mut tx, run_id, reason).await?;
+    }
+
+    tx.commit().await?;
+
+    let improves = |best: Option<u32>| best.is_none_or(|best| run.ticks < best);
+    let flagged = review_reason.is_some();
+    let result = SubmitRunResponse {
+        run_id,
+        nub_pb: improves(bests.nub_pb),
+        pro_pb: is_pro && improves(bests.pro_pb),
+        // Flagged runs aren't records until they're approved.
+        nub_record: !flagged && improves(bests.nub_record),
+        pro_record: !flagged && is_pro && improves(bests.pro_record),
+        flagged,
+    };
+    // Leaderboards only move when somebody's PB does, and flagged runs wait for their review to
+    // do that. The run is stored at this point, so a failure here must not make the server think
+    // it has to submit it again.
+    if !flagged && (result.nub_pb || result.pro_pb) {
+        if let Err(e) = recompute_filter(db.get_ref(), filter_id).await {
+            log::error!("failed to recompute points of filter {filter_id}: {e:?}");
+        }
+        // The replay of the previous PB isn't worth keeping anymore.
+        if let Err(e) = prune_replays(db.get_ref(), storage.get_ref(), filter_id, player_id).await {
+            log::error!("failed to prune replays of filter {filter_id}: {e:?}");
+        }
+    }
+
+    Ok(Json(result))
+}
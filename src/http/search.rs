@@ -1,8 +1,9 @@
-use actix_web::error::Result;
-use actix_web::{get, Responder, HttpResponse};
+use actix_web::get;
 use actix_web::web::{ServiceConfig, Data, Query, Json};
 use serde::Deserialize;
 use sqlx::mysql::MySqlPool;
+use super::error::{ApiError, ApiResult};
+use super::lookup::resolve_mode;
 use super::model::{Map, Player};
 
 pub fn config(conf: &mut ServiceConfig) {
@@ -16,7 +17,7 @@ struct SearchPlayers {
 }
 
 #[get("/search_players")]
-async fn search_players(query: Query<SearchPlayers>, db: Data<MySqlPool>) -> Result<Json<Vec<Player>>> {
+async fn search_players(query: Query<SearchPlayers>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Player>>> {
     // We're using MATCH IN BOOLEAN MODE, which lets you use some operators in the string query. 
     // I don't think they let the user do anything nefarious, but they might get unexpected results,
     // so I prefer nuking them.
@@ -27,7 +28,7 @@ async fn search_players(query: Query<SearchPlayers>, db: Data<MySqlPool>) -> Res
         .collect::<Vec<String>>()
         .join(" ");
     if search_str.is_empty() {
-        return Err(actix_web::error::ErrorBadRequest("insufficient search query"));
+        return Err(ApiError::BadRequest("insufficient search query".to_owned()));
     }
 
     let result: Vec<Player> = sqlx::query_as(r#"
@@ -38,8 +39,7 @@ async fn search_players(query: Query<SearchPlayers>, db: Data<MySqlPool>) -> Res
         LIMIT 20
     "#)
     .bind(&search_str)
-    .fetch_all(db.get_ref()).await
-    .map_err(|_| actix_web::error::ErrorInternalServerError(""))?;
+    .fetch_all(db.get_ref()).await?;
 
     Ok(Json(result))
 }
@@ -51,7 +51,7 @@ struct SearchMaps {
 }
 
 #[get("/search_maps")]
-async fn search_maps(query: Query<SearchMaps>, db: Data<MySqlPool>) -> Result<Json<Vec<Map>>> {
+async fn search_maps(query: Query<SearchMaps>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<Map>>> {
     // We're using MATCH IN BOOLEAN MODE, which lets you use some operators in the string query. 
     // I don't think they let the user do anything nefarious, but they might get unexpected results,
     // so I prefer nuking them.
@@ -63,8 +63,9 @@ async fn search_maps(query: Query<SearchMaps>, db: Data<MySqlPool>) -> Result<Js
         .collect::<Vec<String>>()
         .join(" ");
     if search_str.is_empty() {
-        return Err(actix_web::error::ErrorBadRequest("insufficient search query"));
+        return Err(ApiError::BadRequest("insufficient search query".to_owned()));
     }
+    resolve_mode(db.get_ref(), &query.mode).await?;
 
     let result: Vec<Map> = sqlx::query_as(r#"
         SELECT m.name, m.created_at,
@@ -101,8 +102,7 @@ async fn search_maps(query: Query<SearchMaps>, db: Data<MySqlPool>) -> Result<Js
     "#)
     .bind(&search_str)
     .bind(&query.mode)
-    .fetch_all(db.get_ref()).await
-    .map_err(|_| actix_web::error::ErrorInternalServerError(""))?;
+    .fetch_all(db.get_ref()).await?;
 
     Ok(Json(result))
 }
@@ -0,0 +1,199 @@
+use actix_web::{get, post, HttpResponse};
+use actix_web::web::{ServiceConfig, Json, Data, Path};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use std::net::IpAddr;
+use super::auth_server::Server;
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::model::{GameServer, LiveServer, ServerToken};
+use super::token::{generate_token, hash_token};
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(get_servers)
+        .service(get_all_servers)
+        .service(get_live_servers)
+        .service(heartbeat)
+        .service(register_server)
+        .service(rotate_server_token)
+        .service(disable_server)
+        .service(enable_server);
+}
+
+const SERVER_COLUMNS: &str = r#"
+    s.server_id AS id, s.name, s.owner_id, p.name AS owner_name, s.ip, s.port, s.region,
+    s.disabled, s.created_at
+"#;
+
+/// Approved servers, for players looking for somewhere to play.
+#[get("/servers")]
+async fn get_servers(db: Data<MySqlPool>) -> ApiResult<Json<Vec<GameServer>>> {
+    let result: Vec<GameServer> = sqlx::query_as(&format!(r#"
+        SELECT {SERVER_COLUMNS}
+        FROM servers s
+        LEFT JOIN players p ON p.player_id = s.owner_id
+        WHERE NOT s.disabled
+        ORDER BY s.name
+    "#))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[get("/servers/all")]
+async fn get_all_servers(user: User, db: Data<MySqlPool>) -> ApiResult<Json<Vec<GameServer>>> {
+    user_guard(user.has_permission(Permission::ManageServers))?;
+    let result: Vec<GameServer> = sqlx::query_as(&format!(r#"
+        SELECT {SERVER_COLUMNS}
+        FROM servers s
+        LEFT JOIN players p ON p.player_id = s.owner_id
+        ORDER BY s.name
+    "#))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+/// Servers that haven't sent a heartbeat for this long are shown as offline.
+const HEARTBEAT_TIMEOUT_SECONDS: u32 = 90;
+
+#[derive(Deserialize)]
+struct Heartbeat {
+    map: Option<String>,
+    player_count: u16,
+    max_players: u16,
+    plugin_version: String,
+}
+
+#[post("/servers/heartbeat")]
+async fn heartbeat(server: Server, body: Json<Heartbeat>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    // updated_at is set explicitly, ON UPDATE doesn't fire when nothing else changed.
+    sqlx::query(r#"
+        INSERT INTO server_status (server_id, map, player_count, max_players, plugin_version, updated_at)
+        VALUES (?, ?, ?, ?, ?, NOW())
+        ON DUPLICATE KEY UPDATE
+            map = VALUES(map),
+            player_count = VALUES(player_count),
+            max_players = VALUES(max_players),
+            plugin_version = VALUES(plugin_version),
+            updated_at = NOW()
+    "#)
+    .bind(server.id())
+    .bind(&body.map)
+    .bind(body.player_count)
+    .bind(body.max_players)
+    .bind(&body.plugin_version)
+    .execute(db.get_ref()).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The server browser: every approved server with its last reported state.
+#[get("/servers/live")]
+async fn get_live_servers(db: Data<MySqlPool>) -> ApiResult<Json<Vec<LiveServer>>> {
+    let result: Vec<LiveServer> = sqlx::query_as(r#"
+        SELECT s.server_id AS id, s.name, s.ip, s.port, s.region,
+            ss.map, ss.player_count, ss.max_players, ss.plugin_version,
+            ss.updated_at AS last_heartbeat,
+            COALESCE(ss.updated_at > NOW() - INTERVAL ? SECOND, FALSE) AS online
+        FROM servers s
+        LEFT JOIN server_status ss ON ss.server_id = s.server_id
+        WHERE NOT s.disabled
+        ORDER BY online DESC, ss.player_count DESC, s.name
+    "#)
+    .bind(HEARTBEAT_TIMEOUT_SECONDS)
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct RegisterServer {
+    name: String,
+    owner_id: Option<u64>,
+    ip: Option<IpAddr>,
+    port: Option<u16>,
+    region: Option<String>,
+}
+
+#[post("/servers")]
+async fn register_server(user: User, body: Json<RegisterServer>, db: Data<MySqlPool>) -> ApiResult<Json<ServerToken>> {
+    user_guard(user.has_permission(Permission::ManageServers))?;
+    if body.name.trim().is_empty() || body.name.len() > 64 {
+        return Err(ApiError::BadRequest("server names must be 1 to 64 characters".to_owned()));
+    }
+    if body.port == Some(0) {
+        return Err(ApiError::BadRequest("port must not be 0".to_owned()));
+    }
+    let region = body.region.as_deref().map(str::trim);
+    if region.is_some_and(|r| r.is_empty() || r.len() > 16) {
+        return Err(ApiError::BadRequest("regions must be 1 to 16 characters".to_owned()));
+    }
+    let token = generate_token();
+    let server_id = sqlx::query(r#"
+        INSERT INTO servers (name, owner_id, ip, port, region, token_hash)
+        VALUES (?, ?, ?, ?, ?, ?)
+    "#)
+    .bind(body.name.trim())
+    .bind(body.owner_id)
+    .bind(body.ip.map(|ip| ip.to_string()))
+    .bind(body.port)
+    .bind(region)
+    .bind(hash_token(&token))
+    .execute(db.get_ref()).await?
+    .last_insert_id();
+
+    Ok(Json(ServerToken {
+        server_id: server_id as u32,
+        token,
+    }))
+}
+
+/// Replaces the server's token, the old one stops working immediately.
+#[post("/servers/{server_id}/token")]
+async fn rotate_server_token(user: User, path: Path<u32>, db: Data<MySqlPool>) -> ApiResult<Json<ServerToken>> {
+    user_guard(user.has_permission(Permission::ManageServers))?;
+    let server_id = path.into_inner();
+    let token = generate_token();
+    let updated = sqlx::query("UPDATE servers SET token_hash = ? WHERE server_id = ?")
+        .bind(hash_token(&token))
+        .bind(server_id)
+        .execute(db.get_ref()).await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(ApiError::UnknownServer);
+    }
+
+    Ok(Json(ServerToken {
+        server_id,
+        token,
+    }))
+}
+
+async fn set_disabled(db: &MySqlPool, server_id: u32, disabled: bool) -> ApiResult<HttpResponse> {
+    let server: Option<(u32,)> = sqlx::query_as("SELECT s.server_id FROM servers s WHERE s.server_id = ?")
+        .bind(server_id)
+        .fetch_optional(db).await?;
+    if server.is_none() {
+        return Err(ApiError::UnknownServer);
+    }
+    sqlx::query("UPDATE servers SET disabled = ? WHERE server_id = ?")
+        .bind(disabled)
+        .bind(server_id)
+        .execute(db).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Disabled servers are hidden from the server list and their token is refused.
+#[post("/servers/{server_id}/disable")]
+async fn disable_server(user: User, path: Path<u32>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ManageServers))?;
+    set_disabled(db.get_ref(), path.into_inner(), true).await
+}
+
+#[post("/servers/{server_id}/enable")]
+async fn enable_server(user: User, path: Path<u32>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ManageServers))?;
+    set_disabled(db.get_ref(), path.into_inner(), false).await
+}
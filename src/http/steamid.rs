@@ -0,0 +1,158 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::str::FromStr;
+
+const STEAMID64_BASE: u64 = 76561197960265728;
+
+/// A Steam account, stored by its account id like `players.player_id`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SteamId(u64);
+
+impl SteamId {
+    /// Account ids are 32 bits, anything larger can't be turned back into a SteamID64.
+    fn new(account_id: u64) -> Option<Self> {
+        (1..=u64::from(u32::MAX)).contains(&account_id).then_some(Self(account_id))
+    }
+
+    pub fn from_steamid64(steamid64: u64) -> Option<Self> {
+        steamid64.checked_sub(STEAMID64_BASE).and_then(Self::new)
+    }
+
+    pub fn account_id(&self) -> u64 {
+        self.0
+    }
+
+    pub fn steamid64(&self) -> u64 {
+        self.0 + STEAMID64_BASE
+    }
+
+    /// `STEAM_0:X:Y`
+    pub fn steam2(&self) -> String {
+        format!("STEAM_0:{}:{}", self.0 & 1, self.0 >> 1)
+    }
+
+    /// `[U:1:N]`
+    pub fn steam3(&self) -> String {
+        format!("[U:1:{}]", self.0)
+    }
+
+    pub fn formats(&self) -> SteamIdFormats {
+        SteamIdFormats {
+            steamid64: self.steamid64().to_string(),
+            steam2: self.steam2(),
+            steam3: self.steam3(),
+        }
+    }
+}
+
+/// Accepts a SteamID64, `STEAM_X:Y:Z`, `[U:1:N]` or a bare account id.
+impl FromStr for SteamId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("STEAM_") {
+            // The universe digit is 0 or 1 depending on who formatted it, both mean public.
+            let mut parts = rest.splitn(3, ':');
+            let (Some(universe), Some(y), Some(z)) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(());
+            };
+            if !matches!(universe, "0" | "1") {
+                return Err(());
+            }
+            let y: u64 = y.parse().map_err(|_| ())?;
+            let z: u32 = z.parse().map_err(|_| ())?;
+            if y > 1 {
+                return Err(());
+            }
+            return u64::from(z).checked_mul(2)
+                .and_then(|id| id.checked_add(y))
+                .and_then(Self::new)
+                .ok_or(());
+        }
+        if let Some(account_id) = s.strip_prefix("[U:1:").and_then(|s| s.strip_suffix(']')) {
+            let account_id: u64 = account_id.parse().map_err(|_| ())?;
+            return Self::new(account_id).ok_or(());
+        }
+        let id: u64 = s.parse().map_err(|_| ())?;
+        if id > STEAMID64_BASE {
+            Self::from_steamid64(id).ok_or(())
+        } else {
+            Self::new(id).ok_or(())
+        }
+    }
+}
+
+/// Game servers send either a number or a string in one of the formats `FromStr` takes.
+impl<'de> Deserialize<'de> for SteamId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u64),
+            Text(String),
+        }
+        let raw = Raw::deserialize(deserializer)?;
+        let parsed = match &raw {
+            Raw::Number(id) => id.to_string().parse(),
+            Raw::Text(s) => s.parse(),
+        };
+        parsed.map_err(|_| serde::de::Error::custom("invalid SteamID"))
+    }
+}
+
+#[derive(Serialize)]
+pub struct SteamIdFormats {
+    pub steamid64: String,
+    pub steam2: String,
+    pub steam3: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<u64> {
+        s.parse::<SteamId>().ok().map(|id| id.account_id())
+    }
+
+    #[test]
+    fn parses_every_format() {
+        assert_eq!(parse("76561197960287930"), Some(22202));
+        assert_eq!(parse("STEAM_0:0:11101"), Some(22202));
+        assert_eq!(parse("STEAM_1:1:11101"), Some(22203));
+        assert_eq!(parse("[U:1:22202]"), Some(22202));
+        assert_eq!(parse("22202"), Some(22202));
+    }
+
+    #[test]
+    fn rejects_malformed_ids() {
+        for s in ["", "0", "[U:1:0]", "STEAM_2:0:1", "STEAM_0:2:1", "STEAM_0:0", "[U:2:1]", "-1", "steam"] {
+            assert_eq!(parse(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn rejects_ids_out_of_range() {
+        for s in [
+            "STEAM_0:1:99999999999999999999",
+            "STEAM_0:1:2147483648",
+            "[U:1:4294967296]",
+            "[U:1:18446744073709551615]",
+            "18446744073709551615",
+            "4294967296",
+        ] {
+            assert_eq!(parse(s), None, "{s}");
+        }
+        assert_eq!(parse("STEAM_0:1:2147483647"), Some(u64::from(u32::MAX)));
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        for account_id in [1, 22202, 22203, u64::from(u32::MAX)] {
+            let id = SteamId::new(account_id).unwrap();
+            let formats = id.formats();
+            for s in [formats.steamid64, formats.steam2, formats.steam3] {
+                assert_eq!(s.parse::<SteamId>(), Ok(id), "{s}");
+            }
+        }
+    }
+}
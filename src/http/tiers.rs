@@ -0,0 +1,128 @@
+use actix_web::{get, put};
+use actix_web::web::{ServiceConfig, Json, Data, Query};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::lookup::{resolve_filter, resolve_map, resolve_mode};
+use super::model::{RunKind, TierChange};
+use super::points::recompute_filter;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(set_tier)
+        .service(get_tier_changes);
+}
+
+const MAX_TIER: u32 = 7;
+
+const TIER_CHANGE_COLUMNS: &str = r#"
+    m.name AS map, c.num AS course, m2.short_name AS mode, CAST(tc.kind AS CHAR) AS kind,
+    tc.old_tier, tc.new_tier, tc.changed_by, p.name AS changed_by_name, tc.created_at
+"#;
+
+const TIER_CHANGE_JOINS: &str = r#"
+    INNER JOIN filters f ON f.filter_id = tc.filter_id
+    INNER JOIN courses c ON c.course_id = f.course_id
+    INNER JOIN maps m ON m.map_id = c.map_id
+    INNER JOIN modes m2 ON m2.mode_id = f.mode_id
+    LEFT JOIN players p ON p.player_id = tc.changed_by
+"#;
+
+#[derive(Deserialize)]
+struct SetTier {
+    map: String,
+    course: u32,
+    mode: String,
+    kind: RunKind,
+    /// `None` unrates the course.
+    tier: Option<u32>,
+}
+
+#[put("/tiers")]
+async fn set_tier(user: User, body: Json<SetTier>, db: Data<MySqlPool>) -> ApiResult<Json<TierChange>> {
+    user_guard(user.has_permission(Permission::ApproveMaps))?;
+    if body.tier.is_some_and(|t| t == 0 || t > MAX_TIER) {
+        return Err(ApiError::BadRequest(format!("tiers go from 1 to {MAX_TIER}")));
+    }
+    let filter_id = resolve_filter(db.get_ref(), &body.map, body.course, &body.mode).await?;
+    let tier_column = body.kind.tier_column();
+
+    let mut tx = db.begin().await?;
+    let (old_tier,): (Option<u32>,) = sqlx::query_as(&format!(r#"
+        SELECT f.{tier_column}
+        FROM filters f
+        WHERE f.filter_id = ?
+        FOR UPDATE
+    "#))
+    .bind(filter_id)
+    .fetch_one(&mut tx).await?;
+    sqlx::query(&format!("UPDATE filters SET {tier_column} = ? WHERE filter_id = ?"))
+        .bind(body.tier)
+        .bind(filter_id)
+        .execute(&mut tx).await?;
+    let change_id = sqlx::query(r#"
+        INSERT INTO tier_changes (filter_id, kind, old_tier, new_tier, changed_by)
+        VALUES (?, ?, ?, ?, ?)
+    "#)
+    .bind(filter_id)
+    .bind(body.kind.as_str())
+    .bind(old_tier)
+    .bind(body.tier)
+    .bind(user.id())
+    .execute(&mut tx).await?
+    .last_insert_id();
+    tx.commit().await?;
+
+    // Points scale with the tier.
+    if old_tier != body.tier {
+        if let Err(e) = recompute_filter(db.get_ref(), filter_id).await {
+            log::error!("failed to recompute points of filter {filter_id}: {e:?}");
+        }
+    }
+
+    let result: TierChange = sqlx::query_as(&format!(r#"
+        SELECT {TIER_CHANGE_COLUMNS}
+        FROM tier_changes tc
+        {TIER_CHANGE_JOINS}
+        WHERE tc.change_id = ?
+    "#))
+    .bind(change_id)
+    .fetch_one(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct GetTierChanges {
+    map: Option<String>,
+    mode: Option<String>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+const TIER_CHANGES_DEFAULT_LIMIT: u64 = 50;
+const TIER_CHANGES_MAX_LIMIT: u64 = 200;
+
+#[get("/tiers/changes")]
+async fn get_tier_changes(query: Query<GetTierChanges>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<TierChange>>> {
+    let mut conditions = String::from("1");
+    if let Some(map) = &query.map {
+        conditions += &format!(" AND c.map_id = {}", resolve_map(db.get_ref(), map).await?);
+    }
+    if let Some(mode) = &query.mode {
+        conditions += &format!(" AND f.mode_id = {}", resolve_mode(db.get_ref(), mode).await?);
+    }
+    let result: Vec<TierChange> = sqlx::query_as(&format!(r#"
+        SELECT {TIER_CHANGE_COLUMNS}
+        FROM tier_changes tc
+        {TIER_CHANGE_JOINS}
+        WHERE {conditions}
+        ORDER BY tc.created_at DESC, tc.change_id DESC
+        LIMIT ? OFFSET ?
+    "#))
+    .bind(query.limit.unwrap_or(TIER_CHANGES_DEFAULT_LIMIT).clamp(1, TIER_CHANGES_MAX_LIMIT))
+    .bind(query.offset.unwrap_or(0))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
@@ -0,0 +1,242 @@
+use actix_web::{get, post, put, HttpResponse};
+use actix_web::web::{ServiceConfig, Json, Data, Path, Query};
+use serde::Deserialize;
+use sqlx::{MySql, MySqlPool, Transaction};
+use super::auth_user::{user_guard, Permission, User};
+use super::error::{ApiError, ApiResult};
+use super::invalidation::{invalidate_runs_in, recompute_filters};
+use super::lookup::resolve_map;
+use super::model::RunReview;
+use super::replay_storage::ReplayStorage;
+use super::replays::prune_replays;
+
+pub fn config(conf: &mut ServiceConfig) {
+    conf.service(get_run_reviews)
+        .service(approve_run)
+        .service(reject_run)
+        .service(set_min_ticks);
+}
+
+/// Limits incoming runs are checked against. Runs from disabled servers and banned players
+/// never get this far, the `Server` extractor and `submit_run` turn those away.
+pub struct RunValidation {
+    /// Runs faster than this are refused on courses without their own `min_ticks`.
+    pub min_ticks: u32,
+    /// Records improved by more than this share of the previous record are flagged for review.
+    pub max_record_improvement: f64,
+}
+
+impl RunValidation {
+    /// Refuses runs faster than the course can be finished.
+    pub async fn check_min_ticks(&self, db: &MySqlPool, filter_id: u32, ticks: u32) -> ApiResult<()> {
+        let (min_ticks,): (Option<u32>,) = sqlx::query_as(r#"
+            SELECT c.min_ticks
+            FROM filters f
+            INNER JOIN courses c ON c.course_id = f.course_id
+            WHERE f.filter_id = ?
+        "#)
+        .bind(filter_id)
+        .fetch_one(db).await?;
+        let min_ticks = min_ticks.unwrap_or(self.min_ticks);
+        if ticks < min_ticks {
+            return Err(ApiError::ImplausibleRun(format!("the course can't be finished in less than {min_ticks} ticks")));
+        }
+        Ok(())
+    }
+
+    /// Why a run beating `record` should be looked at by an admin, if it should.
+    pub fn review_reason(&self, kind: &str, ticks: u32, record: Option<u32>) -> Option<String> {
+        let record = record?;
+        let improvement = record.saturating_sub(ticks) as f64 / record as f64;
+        (improvement > self.max_record_improvement)
+            .then(|| format!("improves the {kind} record by {:.1}%", improvement * 100.0))
+    }
+}
+
+/// Condition leaving out runs of the runs table aliased as `alias` that wait for a review.
+/// Suspicious runs stay off the leaderboards, records and points until an admin approves them,
+/// so everything using `not_banned` needs it too.
+pub fn not_pending(alias: &str) -> String {
+    format!(r#"NOT EXISTS (
+        SELECT 1 FROM run_reviews rv
+        WHERE rv.run_id = {alias}.run_id AND rv.status = 'pending'
+    )"#)
+}
+
+pub async fn flag_run(tx: &mut Transaction<'_, MySql>, run_id: u64, reason: &str) -> ApiResult<()> {
+    sqlx::query("INSERT INTO run_reviews (run_id, reason) VALUES (?, ?)")
+        .bind(run_id)
+        .bind(reason)
+        .execute(&mut *tx).await?;
+    Ok(())
+}
+
+const RUN_REVIEW_COLUMNS: &str = r#"
+    rr.review_id AS id, rr.run_id, r.player_id, p.name AS player_name, m.name AS map, c.num AS course,
+    m2.short_name AS mode, r.ticks, r.teleports, r.server_id, r.created_at AS run_created_at,
+    rr.reason, CAST(rr.status AS CHAR) AS status, rr.reviewed_by, rr.reviewed_at, rr.notes, rr.created_at
+"#;
+
+const RUN_REVIEW_JOINS: &str = r#"
+    INNER JOIN runs r ON r.run_id = rr.run_id
+    INNER JOIN filters f ON f.filter_id = r.filter_id
+    INNER JOIN courses c ON c.course_id = f.course_id
+    INNER JOIN maps m ON m.map_id = c.map_id
+    INNER JOIN modes m2 ON m2.mode_id = f.mode_id
+    LEFT JOIN players p ON p.player_id = r.player_id
+"#;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ReviewStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReviewStatus::Pending => "pending",
+            ReviewStatus::Approved => "approved",
+            ReviewStatus::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GetRunReviews {
+    /// Defaults to pending.
+    status: Option<ReviewStatus>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+const RUN_REVIEWS_DEFAULT_LIMIT: u64 = 50;
+const RUN_REVIEWS_MAX_LIMIT: u64 = 200;
+
+/// The review queue, oldest first so nothing waits forever.
+#[get("/runs/reviews")]
+async fn get_run_reviews(user: User, query: Query<GetRunReviews>, db: Data<MySqlPool>) -> ApiResult<Json<Vec<RunReview>>> {
+    user_guard(user.has_permission(Permission::InvalidateRuns))?;
+    let result: Vec<RunReview> = sqlx::query_as(&format!(r#"
+        SELECT {RUN_REVIEW_COLUMNS}
+        FROM run_reviews rr
+        {RUN_REVIEW_JOINS}
+        WHERE rr.status = ?
+        ORDER BY rr.created_at ASC, rr.review_id ASC
+        LIMIT ? OFFSET ?
+    "#))
+    .bind(query.status.unwrap_or(ReviewStatus::Pending).as_str())
+    .bind(query.limit.unwrap_or(RUN_REVIEWS_DEFAULT_LIMIT).clamp(1, RUN_REVIEWS_MAX_LIMIT))
+    .bind(query.offset.unwrap_or(0))
+    .fetch_all(db.get_ref()).await?;
+
+    Ok(Json(result))
+}
+
+#[derive(Deserialize)]
+struct DecideReview {
+    notes: Option<String>,
+}
+
+async fn decide_review(db: &MySqlPool, storage: &dyn ReplayStorage, user: &User, review_id: u32, status: ReviewStatus, notes: Option<&str>) -> ApiResult<RunReview> {
+    user_guard(user.has_permission(Permission::InvalidateRuns))?;
+    let mut tx = db.begin().await?;
+    let review: Option<(String, u64, u32, u64, String)> = sqlx::query_as(r#"
+        SELECT CAST(rr.status AS CHAR), rr.run_id, r.filter_id, r.player_id, rr.reason
+        FROM run_reviews rr
+        INNER JOIN runs r ON r.run_id = rr.run_id
+        WHERE rr.review_id = ?
+        FOR UPDATE
+    "#)
+    .bind(review_id)
+    .fetch_optional(&mut tx).await?;
+    let (run_id, filter_id, player_id, reason) = match review {
+        None => return Err(ApiError::UnknownReview),
+        Some((current, ..)) if current != ReviewStatus::Pending.as_str() => {
+            return Err(ApiError::BadRequest(format!("review was already {current}")));
+        }
+        Some((_, run_id, filter_id, player_id, reason)) => (run_id, filter_id, player_id, reason),
+    };
+    sqlx::query(r#"
+        UPDATE run_reviews
+        SET status = ?, reviewed_by = ?, reviewed_at = NOW(), notes = ?
+        WHERE review_id = ?
+    "#)
+    .bind(status.as_str())
+    .bind(user.id())
+    .bind(notes)
+    .bind(review_id)
+    .execute(&mut tx).await?;
+    // Rejecting only sticks together with the invalidation, or the run would stay up with no
+    // pending review left to decide.
+    if let ReviewStatus::Rejected = status {
+        invalidate_runs_in(&mut tx, user.id(), &format!("r.run_id = {run_id}"), notes.unwrap_or(&reason)).await?;
+    }
+    tx.commit().await?;
+
+    // Either way the run's filter changes: approved runs join the leaderboards, rejected ones
+    // may have displaced a PB while pending.
+    recompute_filters(db, &[filter_id]).await;
+    // Replays of PBs the run beat were kept while it waited, in case it got rejected.
+    if let ReviewStatus::Approved = status {
+        if let Err(e) = prune_replays(db, storage, filter_id, player_id).await {
+            log::error!("failed to prune replays of filter {filter_id}: {e:?}");
+        }
+    }
+
+    let result: RunReview = sqlx::query_as(&format!(r#"
+        SELECT {RUN_REVIEW_COLUMNS}
+        FROM run_reviews rr
+        {RUN_REVIEW_JOINS}
+        WHERE rr.review_id = ?
+    "#))
+    .bind(review_id)
+    .fetch_one(db).await?;
+    Ok(result)
+}
+
+#[post("/runs/reviews/{review_id}/approve")]
+async fn approve_run(user: User, path: Path<u32>, body: Json<DecideReview>, db: Data<MySqlPool>, storage: Data<dyn ReplayStorage>) -> ApiResult<Json<RunReview>> {
+    let review = decide_review(db.get_ref(), storage.get_ref(), &user, path.into_inner(), ReviewStatus::Approved, body.notes.as_deref()).await?;
+    Ok(Json(review))
+}
+
+#[post("/runs/reviews/{review_id}/reject")]
+async fn reject_run(user: User, path: Path<u32>, body: Json<DecideReview>, db: Data<MySqlPool>, storage: Data<dyn ReplayStorage>) -> ApiResult<Json<RunReview>> {
+    let review = decide_review(db.get_ref(), storage.get_ref(), &user, path.into_inner(), ReviewStatus::Rejected, body.notes.as_deref()).await?;
+    Ok(Json(review))
+}
+
+#[derive(Deserialize)]
+struct SetMinTicks {
+    map: String,
+    course: u32,
+    /// `None` falls back to the global minimum.
+    min_ticks: Option<u32>,
+}
+
+#[put("/courses/min_ticks")]
+async fn set_min_ticks(user: User, body: Json<SetMinTicks>, db: Data<MySqlPool>) -> ApiResult<HttpResponse> {
+    user_guard(user.has_permission(Permission::ApproveMaps))?;
+    let map_id = resolve_map(db.get_ref(), &body.map).await?;
+    let updated = sqlx::query("UPDATE courses SET min_ticks = ? WHERE map_id = ? AND num = ?")
+        .bind(body.min_ticks)
+        .bind(map_id)
+        .bind(body.course)
+        .execute(db.get_ref()).await?
+        .rows_affected();
+    if updated == 0 {
+        // MySQL doesn't count rows that already held the value, so look before blaming the course.
+        let course: Option<(u32,)> = sqlx::query_as("SELECT c.course_id FROM courses c WHERE c.map_id = ? AND c.num = ?")
+            .bind(map_id)
+            .bind(body.course)
+            .fetch_optional(db.get_ref()).await?;
+        if course.is_none() {
+            return Err(ApiError::UnknownCourse);
+        }
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
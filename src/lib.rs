@@ -0,0 +1,5 @@
+//! The API as a library, so the integration tests in `tests/` can build the app themselves.
+
+pub mod config;
+pub mod http;
+pub mod schema;
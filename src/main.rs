@@ -1,22 +1,45 @@
 use anyhow::Context;
+use clap::Parser;
 use sqlx::mysql::MySqlPoolOptions;
-use std::env;
-
-mod http;
+use api::config::{Cli, Command, Config};
+use api::{http, schema};
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().ok();
-    env_logger::init();
+    let (command, config) = Config::load(Cli::parse())?;
+    env_logger::Builder::new()
+        .filter_level(config.log_level()?)
+        .parse_default_env()
+        .init();
 
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL not set");
+    // Everything is validated before connecting, except the HTTP settings which `api migrate`
+    // doesn't need.
+    let database = config.database()?;
+    let http = match command {
+        Some(Command::Migrate { .. }) => None,
+        None => Some(config.http()?),
+    };
     let db = MySqlPoolOptions::new()
-        .max_connections(50)
-        .connect(&db_url).await
+        .max_connections(database.max_connections)
+        .acquire_timeout(database.acquire_timeout)
+        .connect(&database.url).await
         .context("could not connect to the database")?;
 
-    //sqlx::migrate!().run(&db).await?;
-    http::serve(db).await?;
+    // `api migrate` only brings the schema up to date, for deployments that migrate as a
+    // separate step. Otherwise `migrate_on_startup` does it before serving.
+    let Some(http) = http else {
+        if let Some(Command::Migrate { baseline: true }) = command {
+            schema::baseline(&db).await?;
+        }
+        return schema::migrate(&db).await;
+    };
+    if database.migrate_on_startup {
+        schema::migrate(&db).await?;
+    }
+    schema::check(&db).await?;
+
+    http::serve(db, http).await?;
 
     Ok(())
 }
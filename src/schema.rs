@@ -0,0 +1,122 @@
+use anyhow::{bail, Context};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::MySqlPool;
+use std::collections::HashMap;
+
+/// Every migration in `migrations/`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub async fn migrate(db: &MySqlPool) -> anyhow::Result<()> {
+    MIGRATOR.run(db).await.context("could not apply the migrations")?;
+    Ok(())
+}
+
+/// The columns and keys of every table in the initial migration, which is what a database from
+/// before the migrations needs to have to be adopted. The leaderboards force the runs indexes
+/// by name, so those have to match too.
+const BASELINE_TABLES: &[(&str, &[&str], &[&str])] = &[
+    ("players", &["player_id", "name", "search_relevance"], &["idx_players__name"]),
+    ("modes", &["mode_id", "name", "short_name"], &["idx_modes__shortname"]),
+    ("maps", &["map_id", "name", "search_tags", "validated", "created_at"], &["idx_maps__name", "idx_maps__searchtags"]),
+    ("mappers", &["mapper_id", "map_id", "player_id"], &["idx_mappers__mapid_playerid", "idx_mappers__playerid"]),
+    ("courses", &["course_id", "map_id", "num"], &["idx_courses__mapid_num"]),
+    ("filters", &["filter_id", "course_id", "mode_id", "nub_tier", "pro_tier"], &["idx_filters__courseid_modeid", "idx_filters__modeid"]),
+    ("servers", &["server_id", "token"], &[]),
+    ("runs", &["run_id", "player_id", "filter_id", "server_id", "ticks", "teleports", "created_at"], &[
+        "idx_runs__filterid_playerid_ticks_createdat",
+        "idx_runs__filterid_tps_playerid_ticks_createdat",
+        "idx_runs__playerid_filterid",
+        "idx_runs__createdat",
+    ]),
+];
+
+/// Records the initial migration as applied to a database whose tables were created by hand,
+/// after checking they look like the ones it creates. Later migrations then run as usual and
+/// bring the data along.
+pub async fn baseline(db: &MySqlPool) -> anyhow::Result<()> {
+    let mut conn = db.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    if !conn.list_applied_migrations().await?.is_empty() {
+        bail!("the database already has a migration history, there is nothing to baseline");
+    }
+
+    let mut problems = Vec::new();
+    for (table, columns, keys) in BASELINE_TABLES {
+        let existing: Vec<(String,)> = sqlx::query_as(r#"
+            SELECT c.COLUMN_NAME
+            FROM information_schema.COLUMNS c
+            WHERE c.TABLE_SCHEMA = DATABASE() AND c.TABLE_NAME = ?
+        "#)
+        .bind(table)
+        .fetch_all(&mut *conn).await?;
+        if existing.is_empty() {
+            problems.push(format!("table {table} is missing"));
+            continue;
+        }
+        let existing_keys: Vec<(String,)> = sqlx::query_as(r#"
+            SELECT DISTINCT s.INDEX_NAME
+            FROM information_schema.STATISTICS s
+            WHERE s.TABLE_SCHEMA = DATABASE() AND s.TABLE_NAME = ?
+        "#)
+        .bind(table)
+        .fetch_all(&mut *conn).await?;
+        for column in columns.iter().filter(|c| !existing.iter().any(|(e,)| e == *c)) {
+            problems.push(format!("column {table}.{column} is missing"));
+        }
+        for key in keys.iter().filter(|k| !existing_keys.iter().any(|(e,)| e == *k)) {
+            problems.push(format!("key {key} on {table} is missing"));
+        }
+    }
+    if !problems.is_empty() {
+        bail!(
+            "the database doesn't match the initial migration, fix it by hand before adopting it: {}",
+            problems.join(", "),
+        );
+    }
+
+    let initial = MIGRATOR.iter().next().context("there are no migrations")?;
+    sqlx::query(r#"
+        INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES (?, ?, TRUE, ?, 0)
+    "#)
+    .bind(initial.version)
+    .bind(&*initial.description)
+    .bind(&*initial.checksum)
+    .execute(&mut *conn).await?;
+    log::info!("recorded migration {} ({}) as applied", initial.version, initial.description);
+    Ok(())
+}
+
+/// Refuses schemas that are behind the binary or were migrated by a different version of it,
+/// the queries would fail at random further down the line otherwise.
+pub async fn check(db: &MySqlPool) -> anyhow::Result<()> {
+    let mut conn = db.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        bail!("migration {version} was interrupted, the schema needs fixing by hand");
+    }
+    let applied: HashMap<i64, Vec<u8>> = conn.list_applied_migrations().await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect();
+    if applied.is_empty() {
+        bail!("the database has no migration history; run `api migrate`, or `api migrate --baseline` if it was created before the API kept its own migrations");
+    }
+
+    for migration in MIGRATOR.iter() {
+        match applied.get(&migration.version) {
+            None => bail!(
+                "the database schema is out of date, migration {} ({}) is missing; run `api migrate`",
+                migration.version,
+                migration.description,
+            ),
+            Some(checksum) if *checksum != *migration.checksum => bail!(
+                "migration {} ({}) differs from the one applied to the database",
+                migration.version,
+                migration.description,
+            ),
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
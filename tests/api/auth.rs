@@ -0,0 +1,165 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::test::TestRequest;
+use serde_json::json;
+use crate::harness::{hash_token, TestApp, ADMIN, ALPHA, MODERATOR};
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn steam_login_redirects_to_steam() {
+    let app = TestApp::start().await;
+
+    let res = app.call(TestRequest::get().uri("/steam_auth")).await;
+    assert_eq!(res.status, StatusCode::PERMANENT_REDIRECT);
+    let location = res.headers.get(header::LOCATION).unwrap().to_str().unwrap();
+    assert!(location.starts_with("https://steamcommunity.com/openid/login"), "{location}");
+    // Nothing signed by Steam, nothing to verify.
+    app.call(TestRequest::get().uri("/steam_auth_verify")).await
+        .expect_error(StatusCode::UNAUTHORIZED, "steam_verification_failed");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn refresh_tokens_are_single_use() {
+    let app = TestApp::start().await;
+
+    sqlx::query(r#"
+        INSERT INTO user_sessions (player_id, refresh_token_hash, expires_at)
+        VALUES (?, ?, NOW() + INTERVAL 1 DAY)
+    "#)
+    .bind(ADMIN)
+    .bind(hash_token("refresh-me"))
+    .execute(&app.db).await.unwrap();
+
+    let refresh = |token: &str| TestRequest::post().uri("/steam_auth_refresh").set_json(json!({ "refresh_token": token }));
+    let body = app.call(refresh("refresh-me")).await.expect(StatusCode::OK);
+    assert_eq!(body["player_id"], ADMIN);
+    let refresh_token = body["refresh_token"].as_str().unwrap().to_owned();
+    assert_ne!(refresh_token, "refresh-me");
+
+    // The new access token carries the admin's permissions.
+    let token = body["token"].as_str().unwrap().to_owned();
+    let protected = || TestRequest::get().uri("/protected").insert_header(("X-User-Token", token.clone()));
+    let body = app.call(protected()).await.expect(StatusCode::OK);
+    assert!(body.as_array().unwrap().contains(&json!("ManageRoles")));
+
+    // Replaying a rotated out token ends the session for everyone holding one of its tokens.
+    app.call(refresh("refresh-me")).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    app.call(refresh(&refresh_token)).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    app.call(protected()).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn user_tokens_are_checked() {
+    let app = TestApp::start().await;
+
+    let protected = || TestRequest::get().uri("/protected");
+    app.call(protected()).await.expect_error(StatusCode::UNAUTHORIZED, "missing_token");
+    app.call(protected().insert_header(("X-User-Token", "garbage"))).await
+        .expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    // Signed, but for a session that was never started.
+    app.call(protected().insert_header(("X-User-Token", app.token(ADMIN, 999)))).await
+        .expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+
+    let alpha = app.login(ALPHA).await;
+    app.call(protected().insert_header(alpha)).await.expect_error(StatusCode::FORBIDDEN, "forbidden");
+    let moderator = app.login(MODERATOR).await;
+    let body = app.call(protected().insert_header(moderator)).await.expect(StatusCode::OK);
+    assert!(body.as_array().unwrap().contains(&json!("ViewBans")));
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn logging_out_revokes_the_session() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    app.call(TestRequest::post().uri("/logout").insert_header(admin.clone())).await.expect(StatusCode::NO_CONTENT);
+    app.call(TestRequest::get().uri("/protected").insert_header(admin)).await
+        .expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn admins_can_revoke_every_session_of_a_player() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let moderator = app.login(MODERATOR).await;
+    let revoke = |player_id: u64| TestRequest::delete().uri(&format!("/players/{player_id}/sessions"));
+
+    app.call(revoke(ADMIN).insert_header(moderator.clone())).await.expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(revoke(MODERATOR).insert_header(admin.clone())).await.expect(StatusCode::NO_CONTENT);
+    app.call(TestRequest::get().uri("/protected").insert_header(moderator)).await
+        .expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    app.call(TestRequest::get().uri("/protected").insert_header(admin)).await.expect(StatusCode::OK);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn roles_grant_permissions_right_away() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let alpha = app.login(ALPHA).await;
+
+    let body = app.call(TestRequest::get().uri("/roles").insert_header(admin.clone())).await.expect(StatusCode::OK);
+    let names: Vec<_> = body.as_array().unwrap().iter().map(|r| r["name"].clone()).collect();
+    assert_eq!(names, [json!("admin"), json!("map_reviewer"), json!("moderator")]);
+    app.call(TestRequest::get().uri("/roles").insert_header(alpha.clone())).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+
+    let roles = |player_id: u64| format!("/players/{player_id}/roles");
+    let body = app.call(TestRequest::get().uri(&roles(MODERATOR)).insert_header(admin.clone())).await.expect(StatusCode::OK);
+    assert_eq!(body, json!(["moderator"]));
+
+    // Alpha's token is the same before and after, permissions are read on every request.
+    let pending = || TestRequest::get().uri("/maps/pending").insert_header(alpha.clone());
+    app.call(pending()).await.expect_error(StatusCode::FORBIDDEN, "forbidden");
+    let grant = TestRequest::put().uri(&format!("{}/map_reviewer", roles(ALPHA))).insert_header(admin.clone());
+    app.call(grant).await.expect(StatusCode::NO_CONTENT);
+    app.call(pending()).await.expect(StatusCode::OK);
+    // Granting twice is fine.
+    let grant = TestRequest::put().uri(&format!("{}/map_reviewer", roles(ALPHA))).insert_header(admin.clone());
+    app.call(grant).await.expect(StatusCode::NO_CONTENT);
+
+    let revoke = || TestRequest::delete().uri(&format!("{}/map_reviewer", roles(ALPHA))).insert_header(admin.clone());
+    app.call(revoke()).await.expect(StatusCode::NO_CONTENT);
+    app.call(pending()).await.expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(revoke()).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+
+    let grant = TestRequest::put().uri(&format!("{}/overlord", roles(ALPHA))).insert_header(admin);
+    app.call(grant).await.expect_error(StatusCode::NOT_FOUND, "unknown_role");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn cors_only_lets_the_site_send_user_tokens() {
+    let app = TestApp::start().await;
+
+    let res = app.call(TestRequest::get().uri("/get_modes").insert_header((header::ORIGIN, "https://elsewhere.example"))).await;
+    assert_eq!(res.status, StatusCode::OK);
+    let admin = app.login(ADMIN).await;
+    let res = app.call(TestRequest::get().uri("/protected")
+        .insert_header((header::ORIGIN, "https://elsewhere.example"))
+        .insert_header(admin.clone())).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = app.call(TestRequest::get().uri("/protected")
+        .insert_header((header::ORIGIN, crate::harness::ORIGIN))
+        .insert_header(admin)).await;
+    assert_eq!(res.status, StatusCode::OK);
+
+    app.finish().await;
+}
@@ -0,0 +1,295 @@
+//! Runs the app against a real MariaDB, since most of what can break lives in the SQL.
+//!
+//! Each test gets a database of its own, migrated and loaded with `tests/fixtures`. The
+//! server comes from `TEST_DATABASE_URL` (any account allowed to create databases) or is
+//! started from the local `mariadbd`/`mysqld` binaries. The tests are ignored by default, run
+//! them with `cargo test -- --ignored`; without a server they fail rather than pass unchecked.
+
+use actix_web::body::MessageBody;
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web::Bytes;
+use actix_web::App;
+use anyhow::{ensure, Context};
+use api::config::HttpConfig;
+use api::http::AppState;
+use api::schema;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
+use sqlx::{Connection, Executor, MySqlConnection, MySqlPool};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+pub const ADMIN: u64 = 1;
+pub const MODERATOR: u64 = 2;
+pub const ALPHA: u64 = 10;
+pub const BRAVO: u64 = 11;
+pub const CHARLIE: u64 = 12;
+/// Banned for good.
+pub const DELTA: u64 = 13;
+/// Has no runs.
+pub const ECHO: u64 = 14;
+
+pub const SERVER_TOKEN: &str = "server-token";
+pub const DISABLED_SERVER_TOKEN: &str = "disabled-server-token";
+pub const ORIGIN: &str = "http://localhost:5000";
+
+const AUTH_TOKEN_SECRET: &str = "integration-tests-secret-of-at-least-32-bytes";
+const FIXTURES: &str = include_str!("../fixtures/leaderboards.sql");
+
+pub struct TestApp {
+    pub db: MySqlPool,
+    state: AppState,
+    server_url: String,
+    database: String,
+    replay_dir: PathBuf,
+}
+
+impl TestApp {
+    /// A fresh database with the fixtures loaded.
+    pub async fn start() -> TestApp {
+        let server_url = server_url();
+        static DATABASES: AtomicU32 = AtomicU32::new(0);
+        let database = format!("api_test_{}_{}", process::id(), DATABASES.fetch_add(1, Ordering::Relaxed));
+        let mut conn = MySqlConnection::connect(&server_url).await
+            .expect("could not connect to the test database server");
+        conn.execute(format!("CREATE DATABASE `{database}`").as_str()).await
+            .expect("could not create the test database");
+        conn.close().await.ok();
+
+        let options = MySqlConnectOptions::from_str(&server_url)
+            .expect("invalid test database URL")
+            .database(&database);
+        let db = MySqlPoolOptions::new()
+            .max_connections(5)
+            .connect_with(options).await
+            .expect("could not connect to the test database");
+        schema::migrate(&db).await.expect("migrations failed");
+        schema::check(&db).await.expect("schema check failed after migrating");
+        db.execute(FIXTURES).await.expect("could not load the fixtures");
+
+        let replay_dir = env::temp_dir().join(&database);
+        let config = HttpConfig {
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            workers: None,
+            tls: None,
+            request_timeout: Duration::from_secs(5),
+            keep_alive: Duration::from_secs(5),
+            shutdown_timeout: Duration::from_secs(5),
+            cors_public_origins: None,
+            cors_allowed_origins: vec![ORIGIN.to_owned()],
+            cors_allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"].map(str::to_owned).to_vec(),
+            cors_allowed_headers: ["Content-Type", "X-User-Token"].map(str::to_owned).to_vec(),
+            auth_token_secret: AUTH_TOKEN_SECRET.to_owned(),
+            steam_realm: ORIGIN.to_owned(),
+            access_token_lifetime: chrono::Duration::hours(2),
+            refresh_token_lifetime: chrono::Duration::days(30),
+            replay_dir: replay_dir.clone(),
+            run_min_ticks: 128,
+            run_max_record_improvement: 0.1,
+        };
+        let state = AppState::new(db.clone(), &config).expect("invalid test config");
+
+        TestApp {
+            db,
+            state,
+            server_url,
+            database,
+            replay_dir,
+        }
+    }
+
+    /// Drops the test's database. Tests that fail before getting here leave it behind to
+    /// be looked at.
+    pub async fn finish(self) {
+        self.db.close().await;
+        let mut conn = MySqlConnection::connect(&self.server_url).await
+            .expect("could not connect to the test database server");
+        conn.execute(format!("DROP DATABASE `{}`", self.database).as_str()).await
+            .expect("could not drop the test database");
+        fs::remove_dir_all(&self.replay_dir).ok();
+    }
+
+    pub async fn call(&self, req: TestRequest) -> Response {
+        let app = test::init_service(
+            App::new()
+                .wrap(self.state.cors())
+                .configure(|conf| self.state.configure(conf))
+        ).await;
+        let res = test::call_service(&app, req.to_request()).await;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.into_body().try_into_bytes().unwrap_or_default();
+        Response { status, headers, body }
+    }
+
+    /// Starts a session for the player, returning the header that authenticates as them.
+    pub async fn login(&self, player_id: u64) -> (&'static str, String) {
+        static SESSIONS: AtomicU32 = AtomicU32::new(0);
+        let refresh_token = format!("{}-{}", self.database, SESSIONS.fetch_add(1, Ordering::Relaxed));
+        let session_id = sqlx::query(r#"
+            INSERT INTO user_sessions (player_id, refresh_token_hash, expires_at)
+            VALUES (?, ?, NOW() + INTERVAL 1 DAY)
+        "#)
+        .bind(player_id)
+        .bind(hash_token(&refresh_token))
+        .execute(&self.db).await
+        .expect("could not create a session")
+        .last_insert_id();
+        ("X-User-Token", self.token(player_id, session_id))
+    }
+
+    /// A signed access token for a session, whether or not it exists.
+    pub fn token(&self, player_id: u64, session_id: u64) -> String {
+        let claims = json!({
+            "user_id": player_id,
+            "session_id": session_id,
+            "permissions": [],
+            "exp": (chrono::Utc::now() + chrono::Duration::hours(1)).timestamp(),
+        });
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(AUTH_TOKEN_SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    pub fn server(&self) -> (&'static str, &'static str) {
+        ("X-Server-Token", SERVER_TOKEN)
+    }
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Response {
+    /// The JSON body, after checking the status.
+    #[track_caller]
+    pub fn expect(&self, status: StatusCode) -> Value {
+        assert_eq!(self.status, status, "unexpected status, body: {}", String::from_utf8_lossy(&self.body));
+        if self.body.is_empty() {
+            return Value::Null;
+        }
+        serde_json::from_slice(&self.body).expect("body is not JSON")
+    }
+
+    /// Checks for one of the API's errors.
+    #[track_caller]
+    pub fn expect_error(&self, status: StatusCode, code: &str) {
+        let body = self.expect(status);
+        assert_eq!(body["error"], code, "unexpected error, body: {body}");
+    }
+}
+
+/// Picks the field out of every element of a JSON array.
+pub fn pluck(body: &Value, field: &str) -> Vec<Value> {
+    body.as_array()
+        .unwrap_or_else(|| panic!("expected an array, got {body}"))
+        .iter()
+        .map(|v| v[field].clone())
+        .collect()
+}
+
+fn server_url() -> String {
+    static SERVER: OnceLock<Result<String, String>> = OnceLock::new();
+    let server = SERVER.get_or_init(|| {
+        if let Ok(url) = env::var("TEST_DATABASE_URL") {
+            return Ok(url);
+        }
+        spawn_server().map_err(|e| format!("{e:#}"))
+    });
+    match server {
+        Ok(url) => url.clone(),
+        Err(e) => panic!("TEST_DATABASE_URL is not set and no local server could be started: {e}"),
+    }
+}
+
+fn find_binary(names: &[&str]) -> Option<PathBuf> {
+    // Server binaries usually live in sbin, which isn't on everyone's PATH.
+    let path = env::var_os("PATH").unwrap_or_default();
+    let dirs = env::split_paths(&path).chain(["/usr/sbin", "/usr/local/sbin"].map(PathBuf::from));
+    dirs.flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Starts a throwaway server listening on localhost, shared by every test of this run.
+fn spawn_server() -> anyhow::Result<String> {
+    let server = find_binary(&["mariadbd", "mysqld"]).context("mariadbd not found")?;
+    let install = find_binary(&["mariadb-install-db", "mysql_install_db"]).context("mariadb-install-db not found")?;
+    let root = env::temp_dir().join(format!("api_test_server_{}", process::id()));
+    let data = root.join("data");
+    fs::create_dir_all(&root)?;
+    // Only root may pass --user, and has to for the server to start at all.
+    let user = fs::metadata("/proc/self")?.uid() == 0;
+    let user = user.then_some("--user=root");
+
+    let status = Command::new(&install)
+        .arg("--no-defaults")
+        .arg(format!("--datadir={}", data.display()))
+        .args(["--auth-root-authentication-method=normal", "--skip-test-db"])
+        .args(user)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    ensure!(status.success(), "{} failed", install.display());
+
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    // The shell stays around to stop the server and clean up once the test process is gone,
+    // statics are never dropped so there is nothing else to do it.
+    Command::new("sh")
+        .arg("-c")
+        .arg(r#"
+            "$0" "$@" >"$ROOT/server.log" 2>&1 &
+            server=$!
+            while kill -0 "$PPID" 2>/dev/null; do sleep 1; done
+            kill "$server"
+            wait "$server"
+            rm -rf "$ROOT"
+        "#)
+        .arg(&server)
+        .arg("--no-defaults")
+        .arg(format!("--datadir={}", data.display()))
+        .arg(format!("--socket={}", root.join("server.sock").display()))
+        .arg(format!("--pid-file={}", root.join("server.pid").display()))
+        .arg(format!("--port={port}"))
+        .args(["--bind-address=127.0.0.1", "--skip-grant-tables", "--skip-log-bin"])
+        .args(user)
+        .env("ROOT", &root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    wait_for_port(port, &root)?;
+    Ok(format!("mysql://root@127.0.0.1:{port}"))
+}
+
+fn wait_for_port(port: u16, root: &Path) -> anyhow::Result<()> {
+    let started = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        ensure!(
+            started.elapsed() < Duration::from_secs(60),
+            "server did not start, see {}",
+            root.join("server.log").display(),
+        );
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
@@ -0,0 +1,14 @@
+//! Integration tests running every route against a real database, see `harness` for how
+//! one is found. They only run with `--ignored`.
+
+mod harness;
+
+mod auth;
+mod maps;
+mod moderation;
+mod players;
+mod public;
+mod replays;
+mod runs;
+mod schema;
+mod servers;
@@ -0,0 +1,150 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use crate::harness::{pluck, TestApp, ADMIN, ALPHA, BRAVO, MODERATOR};
+
+fn new_map(name: &str, courses: &[u32]) -> Value {
+    json!({
+        "name": name,
+        "workshop_id": 2001,
+        "courses": courses,
+        "mappers": [ALPHA, BRAVO, ALPHA],
+    })
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn submitted_maps_wait_for_a_review() {
+    let app = TestApp::start().await;
+
+    let alpha = app.login(ALPHA).await;
+    let admin = app.login(ADMIN).await;
+    let submit = |body: Value| TestRequest::post().uri("/maps").insert_header(alpha.clone()).set_json(body);
+
+    app.call(submit(new_map("kz_new", &[1, 0, 0]))).await.expect(StatusCode::CREATED);
+    app.call(submit(new_map("kz_new", &[0]))).await.expect_error(StatusCode::CONFLICT, "map_exists");
+    app.call(submit(new_map("KZ New", &[0]))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(submit(new_map("kz_empty", &[]))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let mut unknown_mapper = new_map("kz_unknown", &[0]);
+    unknown_mapper["mappers"] = json!([ALPHA, 999]);
+    app.call(submit(unknown_mapper)).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(TestRequest::post().uri("/maps").set_json(new_map("kz_anon", &[0]))).await
+        .expect_error(StatusCode::UNAUTHORIZED, "missing_token");
+
+    let pending = || TestRequest::get().uri("/maps/pending").insert_header(admin.clone());
+    let body = app.call(pending()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_pending"), json!("kz_new")]);
+    assert_eq!(body[1]["workshop_id"], 2001);
+    assert_eq!(body[1]["submitted_by"], ALPHA);
+
+    // Not public until validated, but already there for every mode.
+    let body = app.call(TestRequest::get().uri("/get_maps?mode=VNL")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+    let body = app.call(TestRequest::get().uri("/get_map?mode=VNL&map=kz_new")).await.expect(StatusCode::OK);
+    assert_eq!(body["courses"], json!([
+        { "course": 0, "nub_tier": null, "pro_tier": null },
+        { "course": 1, "nub_tier": null, "pro_tier": null },
+    ]));
+    assert_eq!(pluck(&body["mappers"], "id").len(), 2);
+
+    app.call(TestRequest::post().uri("/maps/kz_new/validate").insert_header(admin.clone())).await
+        .expect(StatusCode::NO_CONTENT);
+    let body = app.call(TestRequest::get().uri("/get_maps?mode=VNL")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_new")]);
+    let body = app.call(pending()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_pending")]);
+
+    let body = app.call(TestRequest::get().uri("/maps/kz_new/reviews").insert_header(admin.clone())).await
+        .expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "action"), [json!("submit"), json!("validate")]);
+    assert_eq!(pluck(&body, "player_id"), [json!(ALPHA), json!(ADMIN)]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn rejected_maps_leave_the_queue() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let reject = |body: Value| TestRequest::post().uri("/maps/kz_pending/reject").insert_header(admin.clone()).set_json(body);
+
+    app.call(reject(json!({}))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(reject(json!({ "reason": "  " }))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(reject(json!({ "reason": "x".repeat(256) }))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(reject(json!({ "reason": "no end zone" }))).await.expect(StatusCode::NO_CONTENT);
+
+    let body = app.call(TestRequest::get().uri("/maps/pending").insert_header(admin.clone())).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+    let body = app.call(TestRequest::get().uri("/maps/kz_pending/reviews").insert_header(admin.clone())).await
+        .expect(StatusCode::OK);
+    assert_eq!(body[0]["action"], "reject");
+    assert_eq!(body[0]["reason"], "no end zone");
+
+    // Rejected maps stay rejected, the log only ever shows decisions that happened.
+    app.call(TestRequest::post().uri("/maps/kz_pending/validate").insert_header(admin.clone())).await
+        .expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(reject(json!({ "reason": "still no end zone" }))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let body = app.call(TestRequest::get().uri("/maps/kz_pending/reviews").insert_header(admin.clone())).await
+        .expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "action"), [json!("reject")]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn unvalidated_maps_are_hidden_again() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let unvalidate = TestRequest::post().uri("/maps/kz_bravo/unvalidate")
+        .insert_header(admin.clone())
+        .set_json(json!({ "reason": "broken after an update" }));
+    app.call(unvalidate).await.expect(StatusCode::NO_CONTENT);
+
+    let body = app.call(TestRequest::get().uri("/get_maps?mode=KZT")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_alpha")]);
+    let body = app.call(TestRequest::get().uri("/maps/pending").insert_header(admin.clone())).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_bravo"), json!("kz_pending")]);
+
+    // Only validated maps can be unvalidated, and only pending ones validated.
+    let unvalidate = |map: &str| TestRequest::post().uri(&format!("/maps/{map}/unvalidate"))
+        .insert_header(admin.clone())
+        .set_json(json!({}));
+    app.call(unvalidate("kz_bravo")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(unvalidate("kz_pending")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(TestRequest::post().uri("/maps/kz_alpha/validate").insert_header(admin.clone())).await
+        .expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(TestRequest::post().uri("/maps/kz_bravo/validate").insert_header(admin)).await
+        .expect(StatusCode::NO_CONTENT);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn reviewing_maps_needs_permission() {
+    let app = TestApp::start().await;
+
+    let alpha = app.login(ALPHA).await;
+    let moderator = app.login(MODERATOR).await;
+    let admin = app.login(ADMIN).await;
+
+    app.call(TestRequest::post().uri("/maps/kz_pending/validate").insert_header(alpha.clone())).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    // Permissions come before the reason is looked at.
+    app.call(TestRequest::post().uri("/maps/kz_pending/reject").insert_header(alpha).set_json(json!({}))).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(TestRequest::get().uri("/maps/pending").insert_header(moderator.clone())).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(TestRequest::get().uri("/maps/kz_alpha/reviews").insert_header(moderator)).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(TestRequest::post().uri("/maps/kz_nope/validate").insert_header(admin.clone())).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_map");
+    app.call(TestRequest::get().uri("/maps/kz_nope/reviews").insert_header(admin)).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_map");
+
+    app.finish().await;
+}
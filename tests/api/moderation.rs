@@ -0,0 +1,322 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use crate::harness::{pluck, TestApp, ADMIN, ALPHA, BRAVO, CHARLIE, DELTA, MODERATOR};
+
+fn maptop() -> TestRequest {
+    TestRequest::get().uri("/get_maptop?map=kz_alpha&course=0&mode=KZT&kind=NUB")
+}
+
+fn run(player_id: u64, ticks: u32, created_at: &str) -> Value {
+    json!({
+        "map": "kz_alpha",
+        "course": 0,
+        "mode": "KZT",
+        "player_id": player_id,
+        "player_name": "someone",
+        "ticks": ticks,
+        "teleports": 0,
+        "created_at": created_at,
+    })
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn bans_are_listed_for_staff() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let bans = |query: &str| TestRequest::get().uri(&format!("/bans?{query}")).insert_header(moderator.clone());
+    let body = app.call(bans("")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(DELTA), json!(CHARLIE)]);
+    assert_eq!(pluck(&body, "active"), [json!(true), json!(false)]);
+    assert_eq!(body[0]["ban_type"], "bhop_hack");
+    assert_eq!(body[0]["player_name"], "delta");
+
+    let body = app.call(bans("active=true")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(DELTA)]);
+    let body = app.call(bans("active=false")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(CHARLIE)]);
+    let body = app.call(bans(&format!("player_id={CHARLIE}"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "reason"), [json!("expired ban")]);
+    let body = app.call(bans("limit=1&offset=1")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(CHARLIE)]);
+
+    let alpha = app.login(ALPHA).await;
+    app.call(TestRequest::get().uri("/bans").insert_header(alpha)).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn bans_take_players_off_the_leaderboards() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let ban = TestRequest::post().uri("/bans").insert_header(moderator.clone()).set_json(json!({
+        "player_id": BRAVO,
+        "ban_type": "strafe_hack",
+        "reason": "perfect strafes",
+    }));
+    let body = app.call(ban).await.expect(StatusCode::OK);
+    let ban_id = body["id"].as_u64().unwrap();
+    assert_eq!(body["active"], true);
+    assert_eq!(body["banned_by"], MODERATOR);
+    assert_eq!(body["expires_at"], json!(null));
+
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(ALPHA), json!(CHARLIE)]);
+    assert_eq!(pluck(&body, "rank"), [json!(1), json!(1)]);
+    let submit = TestRequest::post().uri("/runs").insert_header(app.server()).set_json(run(BRAVO, 9500, "2026-02-01T00:00:00Z"));
+    app.call(submit).await.expect_error(StatusCode::FORBIDDEN, "player_banned");
+
+    let update = |body: Value| TestRequest::patch().uri(&format!("/bans/{ban_id}")).insert_header(moderator.clone()).set_json(body);
+    let body = app.call(update(json!({ "notes": "appealed" }))).await.expect(StatusCode::OK);
+    assert_eq!(body["notes"], "appealed");
+    assert_eq!(body["reason"], "perfect strafes");
+    assert_eq!(body["active"], true);
+    let body = app.call(update(json!({ "expires_at": "2026-01-01T00:00:00Z" }))).await.expect(StatusCode::OK);
+    assert_eq!(body["active"], false);
+    assert_eq!(body["notes"], "appealed");
+    let body = app.call(update(json!({ "expires_at": null, "notes": null, "ban_type": "exploit" }))).await.expect(StatusCode::OK);
+    assert_eq!(body["active"], true);
+    assert_eq!(body["notes"], json!(null));
+    assert_eq!(body["ban_type"], "exploit");
+
+    let expire = || TestRequest::post().uri(&format!("/bans/{ban_id}/expire")).insert_header(moderator.clone());
+    let body = app.call(expire()).await.expect(StatusCode::OK);
+    assert_eq!(body["active"], false);
+    let expired_at = body["expires_at"].clone();
+    // Expiring twice keeps the first end date.
+    let body = app.call(expire()).await.expect(StatusCode::OK);
+    assert_eq!(body["expires_at"], expired_at);
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id")[0], BRAVO);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn bans_are_checked() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let alpha = app.login(ALPHA).await;
+    let ban = json!({ "player_id": BRAVO, "ban_type": "wallhack", "reason": "?" });
+    app.call(TestRequest::post().uri("/bans").insert_header(moderator.clone()).set_json(ban)).await
+        .expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let ban = json!({ "player_id": BRAVO, "ban_type": "other", "reason": "?" });
+    app.call(TestRequest::post().uri("/bans").insert_header(alpha).set_json(ban)).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(TestRequest::patch().uri("/bans/999").insert_header(moderator.clone()).set_json(json!({}))).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_ban");
+    app.call(TestRequest::post().uri("/bans/999/expire").insert_header(moderator)).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_ban");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn invalidated_runs_can_be_restored() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let invalidate = |run_id: u64, reason: &str| TestRequest::post()
+        .uri(&format!("/runs/{run_id}/invalidate"))
+        .insert_header(moderator.clone())
+        .set_json(json!({ "reason": reason }));
+    let restore = |run_id: u64| TestRequest::post().uri(&format!("/runs/{run_id}/restore")).insert_header(moderator.clone());
+
+    let body = app.call(invalidate(110, "skipped the course")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!({ "runs": 1 }));
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks")[0], 10200);
+    let body = app.call(invalidate(110, "again")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!({ "runs": 0 }));
+
+    app.call(restore(110)).await.expect(StatusCode::NO_CONTENT);
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks")[0], 9800);
+    app.call(restore(110)).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+
+    app.call(invalidate(110, " ")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(invalidate(999, "gone")).await.expect_error(StatusCode::NOT_FOUND, "unknown_run");
+    app.call(restore(999)).await.expect_error(StatusCode::NOT_FOUND, "unknown_run");
+    let alpha = app.login(ALPHA).await;
+    let res = app.call(TestRequest::post().uri("/runs/110/invalidate").insert_header(alpha).set_json(json!({ "reason": "mine" }))).await;
+    res.expect_error(StatusCode::FORBIDDEN, "forbidden");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn course_runs_are_invalidated_in_bulk() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let invalidate = |body: Value| TestRequest::post().uri("/runs/invalidate").insert_header(moderator.clone()).set_json(body);
+
+    let body = app.call(invalidate(json!({
+        "map": "kz_alpha", "course": 0, "mode": "KZT", "player_id": ALPHA, "reason": "cheated",
+    }))).await.expect(StatusCode::OK);
+    assert_eq!(body, json!({ "runs": 6 }));
+    let uri = format!("/get_course_pb_history?player_id={ALPHA}&map=kz_alpha&course=0&mode=KZT&kind=NUB");
+    let body = app.call(TestRequest::get().uri(&uri)).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    // Every mode when none is given, SKZ included.
+    let body = app.call(invalidate(json!({
+        "map": "kz_alpha", "course": 0, "before": "2026-01-09T00:00:00Z", "reason": "skip fixed",
+    }))).await.expect(StatusCode::OK);
+    assert_eq!(body, json!({ "runs": 2 }));
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(CHARLIE)]);
+    assert_eq!(pluck(&body, "run_id"), [json!(110), json!(109)]);
+
+    app.call(invalidate(json!({ "map": "kz_alpha", "course": 0, "reason": "everything" }))).await
+        .expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(invalidate(json!({ "map": "kz_alpha", "course": 9, "player_id": ALPHA, "reason": "x" }))).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_course");
+    app.call(invalidate(json!({ "map": "kz_nope", "course": 0, "player_id": ALPHA, "reason": "x" }))).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_map");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn flagged_runs_are_approved_or_rejected() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let submit = |body: Value| TestRequest::post().uri("/runs").insert_header(app.server()).set_json(body);
+    let reviews = |query: &str| TestRequest::get().uri(&format!("/runs/reviews?{query}")).insert_header(moderator.clone());
+
+    let flagged = app.call(submit(run(CHARLIE, 7500, "2026-02-01T00:00:00Z"))).await.expect(StatusCode::OK);
+    assert_eq!(flagged["flagged"], true);
+    let body = app.call(reviews("")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "run_id"), [flagged["run_id"].clone()]);
+    assert_eq!(body[0]["reason"], "improves the NUB record by 16.7%");
+    assert_eq!(body[0]["status"], "pending");
+    assert_eq!(body[0]["map"], "kz_alpha");
+    assert_eq!(body[0]["server_id"], 1);
+    // Pending runs stay off the leaderboard.
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_ne!(pluck(&body, "ticks")[0], 7500);
+
+    let decide = |review_id: &Value, decision: &str, notes: Value| TestRequest::post()
+        .uri(&format!("/runs/reviews/{review_id}/{decision}"))
+        .insert_header(moderator.clone())
+        .set_json(json!({ "notes": notes }));
+    let review_id = body[0]["id"].clone();
+    let body = app.call(decide(&review_id, "approve", json!("checked the demo"))).await.expect(StatusCode::OK);
+    assert_eq!(body["status"], "approved");
+    assert_eq!(body["reviewed_by"], MODERATOR);
+    assert_eq!(body["notes"], "checked the demo");
+    app.call(decide(&review_id, "reject", json!(null))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks")[0], 7500);
+
+    // Rejecting invalidates the run.
+    let flagged = app.call(submit(run(ALPHA, 5000, "2026-02-02T00:00:00Z"))).await.expect(StatusCode::OK);
+    assert_eq!(flagged["flagged"], true);
+    let body = app.call(reviews("")).await.expect(StatusCode::OK);
+    let review_id = body[0]["id"].clone();
+    let body = app.call(decide(&review_id, "reject", json!(null))).await.expect(StatusCode::OK);
+    assert_eq!(body["status"], "rejected");
+    let (reason,): (Option<String>,) = sqlx::query_as("SELECT invalidation_reason FROM runs WHERE run_id = ?")
+        .bind(flagged["run_id"].as_u64().unwrap())
+        .fetch_one(&app.db).await.unwrap();
+    assert_eq!(reason.as_deref(), Some("improves the NUB record by 33.3%"));
+    let body = app.call(maptop()).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks")[0], 7500);
+
+    let body = app.call(reviews("")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+    let body = app.call(reviews("status=approved")).await.expect(StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    let body = app.call(reviews("status=rejected")).await.expect(StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    app.call(decide(&json!(999), "approve", json!(null))).await.expect_error(StatusCode::NOT_FOUND, "unknown_review");
+    let alpha = app.login(ALPHA).await;
+    app.call(TestRequest::get().uri("/runs/reviews").insert_header(alpha)).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn courses_can_have_their_own_minimum_time() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let set = |body: Value| TestRequest::put().uri("/courses/min_ticks").insert_header(admin.clone()).set_json(body);
+    let submit = |ticks: u32, created_at: &str| TestRequest::post().uri("/runs").insert_header(app.server()).set_json(run(ALPHA, ticks, created_at));
+
+    app.call(set(json!({ "map": "kz_alpha", "course": 0, "min_ticks": 10450 }))).await.expect(StatusCode::NO_CONTENT);
+    // Setting the same value again changes no rows but is still fine.
+    app.call(set(json!({ "map": "kz_alpha", "course": 0, "min_ticks": 10450 }))).await.expect(StatusCode::NO_CONTENT);
+    app.call(submit(10400, "2026-02-01T00:00:00Z")).await.expect_error(StatusCode::UNPROCESSABLE_ENTITY, "implausible_run");
+    app.call(submit(10450, "2026-02-01T00:00:00Z")).await.expect(StatusCode::OK);
+
+    app.call(set(json!({ "map": "kz_alpha", "course": 0, "min_ticks": null }))).await.expect(StatusCode::NO_CONTENT);
+    app.call(submit(10400, "2026-02-02T00:00:00Z")).await.expect(StatusCode::OK);
+
+    app.call(set(json!({ "map": "kz_alpha", "course": 9, "min_ticks": 100 }))).await
+        .expect_error(StatusCode::NOT_FOUND, "unknown_course");
+    let moderator = app.login(MODERATOR).await;
+    let res = app.call(TestRequest::put().uri("/courses/min_ticks").insert_header(moderator)
+        .set_json(json!({ "map": "kz_alpha", "course": 0, "min_ticks": 100 }))).await;
+    res.expect_error(StatusCode::FORBIDDEN, "forbidden");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn tier_changes_are_recorded() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let set = |body: Value| TestRequest::put().uri("/tiers").insert_header(admin.clone()).set_json(body);
+
+    let body = app.call(set(json!({ "map": "kz_alpha", "course": 0, "mode": "KZT", "kind": "NUB", "tier": 7 }))).await
+        .expect(StatusCode::OK);
+    assert_eq!(body["old_tier"], 3);
+    assert_eq!(body["new_tier"], 7);
+    assert_eq!(body["kind"], "NUB");
+    assert_eq!(body["changed_by_name"], "admin");
+    app.call(set(json!({ "map": "kz_bravo", "course": 0, "mode": "KZT", "kind": "PRO", "tier": null }))).await
+        .expect(StatusCode::OK);
+
+    let body = app.call(TestRequest::get().uri("/get_map?mode=KZT&map=kz_alpha")).await.expect(StatusCode::OK);
+    assert_eq!(body["courses"][0]["nub_tier"], 7);
+
+    let changes = |query: &str| TestRequest::get().uri(&format!("/tiers/changes?{query}"));
+    let body = app.call(changes("")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "map"), [json!("kz_bravo"), json!("kz_alpha")]);
+    assert_eq!(body[0]["new_tier"], json!(null));
+    let body = app.call(changes("map=kz_alpha")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "new_tier"), [json!(7)]);
+    let body = app.call(changes("mode=SKZ")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+    app.call(changes("map=kz_nope")).await.expect_error(StatusCode::NOT_FOUND, "unknown_map");
+
+    app.call(set(json!({ "map": "kz_alpha", "course": 0, "mode": "KZT", "kind": "NUB", "tier": 8 }))).await
+        .expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(set(json!({ "map": "kz_alpha", "course": 1, "mode": "SKZ", "kind": "NUB", "tier": 2 }))).await
+        .expect_error(StatusCode::NOT_FOUND, "course_not_available_in_mode");
+    let moderator = app.login(MODERATOR).await;
+    let res = app.call(TestRequest::put().uri("/tiers").insert_header(moderator)
+        .set_json(json!({ "map": "kz_alpha", "course": 0, "mode": "KZT", "kind": "NUB", "tier": 1 }))).await;
+    res.expect_error(StatusCode::FORBIDDEN, "forbidden");
+
+    app.finish().await;
+}
@@ -0,0 +1,234 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::json;
+use crate::harness::{pluck, TestApp, ADMIN, ALPHA, BRAVO, CHARLIE, DELTA, ECHO};
+
+fn get(uri: &str) -> TestRequest {
+    TestRequest::get().uri(uri)
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn profiles_sum_up_a_players_runs() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get(&format!("/players/{ALPHA}"))).await.expect(StatusCode::OK);
+    assert_eq!(body["name"], "alpha");
+    assert_eq!(body["steamid"]["steamid64"], "76561197960265738");
+    assert_eq!(body["banned"], false);
+    // 107 is invalidated.
+    assert_eq!(body["total_runs"], 8);
+    assert_eq!(body["first_seen"], "2026-01-01T10:00:00Z");
+    assert_eq!(body["last_seen"], "2026-01-06T10:00:00Z");
+    let stats = body["stats"].as_array().unwrap();
+    assert_eq!(pluck(&body["stats"], "mode"), [json!("KZT"), json!("KZT"), json!("SKZ"), json!("SKZ")]);
+    assert_eq!(stats[0]["kind"], "NUB");
+    assert_eq!(stats[0]["completed_maps"], 2);
+    assert_eq!(stats[0]["completed_courses"], 2);
+    assert_eq!(stats[0]["records"], 1);
+    assert_eq!(stats[1]["kind"], "PRO");
+    assert_eq!(stats[1]["completed_courses"], 2);
+    assert_eq!(stats[2]["records"], 1);
+
+    // Every SteamID format finds the same player.
+    for steamid in ["76561197960265738", "STEAM_1:0:5", "[U:1:10]"] {
+        let body = app.call(get(&format!("/players/{steamid}"))).await.expect(StatusCode::OK);
+        assert_eq!(body["id"], ALPHA);
+    }
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn profiles_show_bans_and_unknown_players() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get(&format!("/players/{DELTA}"))).await.expect(StatusCode::OK);
+    assert_eq!(body["banned"], true);
+    // Charlie's ban ran out.
+    let body = app.call(get(&format!("/players/{CHARLIE}"))).await.expect(StatusCode::OK);
+    assert_eq!(body["banned"], false);
+    let body = app.call(get(&format!("/players/{ECHO}"))).await.expect(StatusCode::OK);
+    assert_eq!(body["total_runs"], 0);
+    assert_eq!(body["stats"], json!([]));
+
+    app.call(get("/players/999")).await.expect_error(StatusCode::NOT_FOUND, "unknown_player");
+    app.call(get("/players/nobody")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn compare_puts_shared_courses_side_by_side() {
+    let app = TestApp::start().await;
+
+    let uri = format!("/players/compare?player_a={ALPHA}&player_b={BRAVO}&mode=KZT&kind=NUB");
+    let body = app.call(get(&uri)).await.expect(StatusCode::OK);
+    assert_eq!(body["player_a"]["name"], "alpha");
+    assert_eq!(body["player_b"]["name"], "bravo");
+    assert_eq!(body["shared_courses"], 2);
+    assert_eq!(body["player_a_faster"], 1);
+    assert_eq!(body["player_b_faster"], 1);
+    assert_eq!(body["ties"], 0);
+    let courses = &body["courses"];
+    assert_eq!(pluck(courses, "map"), [json!("kz_alpha"), json!("kz_bravo")]);
+    assert_eq!(pluck(courses, "delta_ticks"), [json!(700), json!(-200)]);
+    assert_eq!(courses[0]["player_a"], json!({ "ticks": 10500, "teleports": 0, "rank": 2 }));
+    assert_eq!(courses[0]["player_b"], json!({ "ticks": 9800, "teleports": 2, "rank": 1 }));
+
+    // Banned players can still be compared.
+    let uri = format!("/players/compare?player_a={DELTA}&player_b={ALPHA}&mode=KZT&kind=PRO");
+    let body = app.call(get(&uri)).await.expect(StatusCode::OK);
+    assert_eq!(body["courses"][0]["player_a"]["rank"], 1);
+
+    // A course charlie only finished with a run waiting for review isn't shared yet.
+    sqlx::query("INSERT INTO runs (run_id, player_id, filter_id, server_id, ticks, teleports, created_at) VALUES (130, ?, 4, 1, 7000, 0, '2026-01-06 12:00:00')")
+        .bind(CHARLIE)
+        .execute(&app.db).await.unwrap();
+    sqlx::query("INSERT INTO run_reviews (run_id, reason) VALUES (130, 'test')")
+        .execute(&app.db).await.unwrap();
+    let uri = format!("/players/compare?player_a={ALPHA}&player_b={CHARLIE}&mode=KZT&kind=NUB");
+    let body = app.call(get(&uri)).await.expect(StatusCode::OK);
+    assert_eq!(body["shared_courses"], 1);
+    assert_eq!(pluck(&body["courses"], "map"), [json!("kz_alpha")]);
+
+    let uri = format!("/players/compare?player_a={ALPHA}&player_b={ALPHA}&mode=KZT&kind=NUB");
+    app.call(get(&uri)).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let uri = format!("/players/compare?player_a={ALPHA}&player_b=999&mode=KZT&kind=NUB");
+    app.call(get(&uri)).await.expect_error(StatusCode::NOT_FOUND, "unknown_player");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn pbs_place_the_player_on_every_course() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get(&format!("/players/{ALPHA}/pbs?mode=KZT&kind=NUB"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "map"), [json!("kz_alpha"), json!("kz_bravo")]);
+    assert_eq!(pluck(&body, "run_id"), [json!(105), json!(401)]);
+    assert_eq!(pluck(&body, "rank"), [json!(2), json!(1)]);
+    assert_eq!(pluck(&body, "record_ticks"), [json!(9800), json!(8000)]);
+    assert_eq!(pluck(&body, "gap_ticks"), [json!(700), json!(0)]);
+    assert_eq!(pluck(&body, "tier"), [json!(3), json!(1)]);
+    assert_eq!(pluck(&body, "has_pro"), [json!(true), json!(true)]);
+
+    let pbs = |query: &str| get(&format!("/players/{ALPHA}/pbs?{query}"));
+    let body = app.call(pbs("kind=NUB&sort=time")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(8000), json!(10500), json!(20000)]);
+    let body = app.call(pbs("kind=NUB&mode=KZT&sort=rank&limit=1")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "map"), [json!("kz_bravo")]);
+    let body = app.call(pbs("kind=NUB&mode=KZT&tier=3")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "map"), [json!("kz_alpha")]);
+    let body = app.call(pbs("kind=NUB&mode=KZT&completion=tp_only")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    // Bravo only finished kz_alpha course 1 with teleports.
+    let body = app.call(get(&format!("/players/{BRAVO}/pbs?mode=KZT&kind=NUB&completion=tp_only"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "course"), [json!(1)]);
+    let body = app.call(get(&format!("/players/{BRAVO}/pbs?mode=KZT&kind=PRO"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10200), json!(8200)]);
+
+    // A faster run waiting for review isn't the PB yet.
+    sqlx::query("INSERT INTO runs (run_id, player_id, filter_id, server_id, ticks, teleports, created_at) VALUES (130, ?, 1, 1, 9000, 0, '2026-02-01 12:00:00')")
+        .bind(ALPHA)
+        .execute(&app.db).await.unwrap();
+    sqlx::query("INSERT INTO run_reviews (run_id, reason) VALUES (130, 'test')")
+        .execute(&app.db).await.unwrap();
+    let body = app.call(get(&format!("/players/{ALPHA}/pbs?mode=KZT&kind=NUB"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "run_id"), [json!(105), json!(401)]);
+    assert_eq!(pluck(&body, "ticks"), [json!(10500), json!(8000)]);
+
+    app.call(pbs("kind=NUB&mode=XYZ")).await.expect_error(StatusCode::NOT_FOUND, "unknown_mode");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn unfinished_lists_validated_courses_left_to_do() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get(&format!("/players/{ALPHA}/unfinished?mode=KZT&kind=NUB"))).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([{ "map": "kz_alpha", "course": 1, "tier": 5 }]));
+    let body = app.call(get(&format!("/players/{ECHO}/unfinished?mode=KZT&kind=PRO"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "tier"), [json!(2), json!(4), json!(6)]);
+    let body = app.call(get(&format!("/players/{ECHO}/unfinished?mode=KZT&kind=PRO&tier=4"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "map"), [json!("kz_alpha")]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn points_follow_recomputes() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    app.call(TestRequest::post().uri("/rankings/recompute").insert_header(admin.clone())).await
+        .expect(StatusCode::ACCEPTED);
+    // It carries on in the background.
+    let status = || TestRequest::get().uri("/rankings/recompute").insert_header(admin.clone());
+    while app.call(status()).await.expect(StatusCode::OK)["running"] == true {
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    let body = app.call(get("/rankings?mode=KZT&kind=NUB")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(ALPHA), json!(CHARLIE)]);
+    assert_eq!(pluck(&body, "points"), [json!(5841), json!(2640), json!(1640)]);
+    assert_eq!(pluck(&body, "courses"), [json!(3), json!(2), json!(1)]);
+    let body = app.call(get("/rankings?mode=KZT&kind=NUB&offset=1&limit=1")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "rank"), [json!(2)]);
+
+    let body = app.call(get(&format!("/players/{ALPHA}/points?mode=KZT&kind=NUB"))).await.expect(StatusCode::OK);
+    assert_eq!(body["points"], 2640);
+    assert_eq!(pluck(&body["courses"], "map"), [json!("kz_alpha"), json!("kz_bravo")]);
+    assert_eq!(pluck(&body["courses"], "placement"), [json!(2), json!(1)]);
+    let body = app.call(get(&format!("/players/{DELTA}/points?mode=KZT&kind=NUB"))).await.expect(StatusCode::OK);
+    assert_eq!(body["points"], 0);
+
+    // Totals only move by what changed, so recomputing again leaves them be.
+    app.call(TestRequest::post().uri("/rankings/recompute").insert_header(admin.clone())).await
+        .expect(StatusCode::ACCEPTED);
+    while app.call(status()).await.expect(StatusCode::OK)["running"] == true {
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let body = app.call(get("/rankings?mode=KZT&kind=NUB")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "points"), [json!(5841), json!(2640), json!(1640)]);
+    assert_eq!(pluck(&body, "courses"), [json!(3), json!(2), json!(1)]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn recomputing_rankings_needs_permission() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(crate::harness::MODERATOR).await;
+    app.call(TestRequest::post().uri("/rankings/recompute").insert_header(moderator.clone())).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(TestRequest::get().uri("/rankings/recompute").insert_header(moderator)).await
+        .expect_error(StatusCode::FORBIDDEN, "forbidden");
+    app.call(TestRequest::post().uri("/rankings/recompute")).await
+        .expect_error(StatusCode::UNAUTHORIZED, "missing_token");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn player_jumpstats_keep_the_longest_of_each_type() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get(&format!("/players/{ALPHA}/jumpstats?mode=KZT"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "jump_type"), [json!("bhop"), json!("longjump")]);
+    assert_eq!(pluck(&body, "distance"), [json!(240.0), json!(250.5)]);
+    let body = app.call(get(&format!("/players/{ALPHA}/jumpstats?mode=KZT&binded=true"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "distance"), [json!(240.0), json!(260.0)]);
+
+    app.finish().await;
+}
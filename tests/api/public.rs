@@ -0,0 +1,167 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::json;
+use crate::harness::{pluck, TestApp, ALPHA, BRAVO};
+
+fn get(uri: &str) -> TestRequest {
+    TestRequest::get().uri(uri)
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn modes_are_listed() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get("/get_modes")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "short_name"), [json!("KZT"), json!("SKZ"), json!("VNL")]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn maps_come_with_their_courses_and_mappers() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get("/get_map?mode=KZT&map=kz_alpha")).await.expect(StatusCode::OK);
+    assert_eq!(body["name"], "kz_alpha");
+    assert_eq!(body["courses"], json!([
+        { "course": 0, "nub_tier": 3, "pro_tier": 4 },
+        { "course": 1, "nub_tier": 5, "pro_tier": 6 },
+    ]));
+    assert_eq!(body["mappers"], json!([{ "id": ALPHA, "name": "alpha" }]));
+
+    let body = app.call(get("/get_map?mode=SKZ&map=kz_alpha")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body["courses"], "course"), [json!(0)]);
+
+    app.call(get("/get_map?mode=KZT&map=kz_nope")).await.expect_error(StatusCode::NOT_FOUND, "unknown_map");
+    app.call(get("/get_map?mode=XYZ&map=kz_alpha")).await.expect_error(StatusCode::NOT_FOUND, "unknown_mode");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn map_lists_only_have_validated_maps() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get("/get_maps?mode=KZT")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_alpha"), json!("kz_bravo")]);
+    assert_eq!(body[1]["mappers"], json!([{ "id": BRAVO, "name": "bravo" }]));
+    let body = app.call(get("/get_maps?mode=SKZ")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_alpha")]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn search_finds_players_and_maps() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get("/search_players?query=alp")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([{ "id": ALPHA, "name": "alpha" }]));
+    app.call(get("/search_players?query=a")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+
+    let body = app.call(get("/search_maps?query=bravo&mode=KZT")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("kz_bravo")]);
+    assert_eq!(body[0]["mappers"], json!([{ "id": BRAVO, "name": "bravo" }]));
+    app.call(get("/search_maps?query=()&mode=KZT")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(get("/search_maps?query=bravo&mode=XYZ")).await.expect_error(StatusCode::NOT_FOUND, "unknown_mode");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn recent_records_beat_everyone_before_them() {
+    let app = TestApp::start().await;
+
+    // Feeds only look 30 days back from where they start, which is now by default.
+    let body = app.call(get("/records/recent")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    let body = app.call(get("/records/recent?before=2026-01-20T00:00:00Z")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [
+        json!(9800), json!(10200), json!(10500), json!(8000),
+        json!(15000), json!(20000), json!(11000), json!(12000),
+    ]);
+
+    let body = app.call(get("/records/recent?mode=KZT&map=kz_alpha&limit=2&before=2026-01-20T00:00:00Z")).await
+        .expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(BRAVO)]);
+    assert_eq!(body[0]["previous"], json!({ "player_id": BRAVO, "player_name": "bravo", "ticks": 10200 }));
+    assert_eq!(body[0]["improvement_ticks"], 400);
+    assert_eq!(body[1]["previous"]["player_id"], ALPHA);
+
+    // The next page starts after the last run of this one.
+    let body = app.call(get("/records/recent?kind=PRO&mode=KZT&tier=4&limit=2&before=2026-01-20T00:00:00Z")).await
+        .expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10200), json!(10500)]);
+    let next = format!(
+        "/records/recent?kind=PRO&mode=KZT&tier=4&limit=2&before=2026-01-05T10:00:00Z&before_run_id={}",
+        body[1]["run_id"],
+    );
+    let body = app.call(get(&next)).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(11500)]);
+    assert_eq!(body[0]["previous"], json!(null));
+
+    app.call(get("/records/recent?map=kz_nope")).await.expect_error(StatusCode::NOT_FOUND, "unknown_map");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn recent_runs_are_the_players_own_pbs() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get(&format!("/runs/recent?mode=KZT&player_id={ALPHA}&before=2026-01-20T00:00:00Z"))).await
+        .expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10500), json!(8000), json!(11000), json!(12000)]);
+    assert_eq!(body[0]["previous"]["ticks"], 11000);
+    assert_eq!(body[0]["improvement_ticks"], 500);
+    assert_eq!(pluck(&body, "map")[1], "kz_bravo");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn jumpstat_top_keeps_each_players_longest_jump() {
+    let app = TestApp::start().await;
+
+    // Delta's 290 is left out, delta is banned.
+    let body = app.call(get("/jumpstats?mode=KZT&jump_type=longjump")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(ALPHA)]);
+    assert_eq!(pluck(&body, "distance"), [json!(255.0), json!(250.5)]);
+    assert_eq!(pluck(&body, "rank"), [json!(1), json!(2)]);
+
+    let body = app.call(get("/jumpstats?mode=KZT&jump_type=longjump&binded=true")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "distance"), [json!(260.0), json!(255.0)]);
+    let body = app.call(get("/jumpstats?mode=KZT&jump_type=longjump&offset=1")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(ALPHA)]);
+    let body = app.call(get("/jumpstats?mode=SKZ&jump_type=longjump")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    app.call(get("/jumpstats?mode=KZT&jump_type=highjump")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn server_lists_leave_out_disabled_servers() {
+    let app = TestApp::start().await;
+
+    let body = app.call(get("/servers")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("Test Server")]);
+    assert_eq!(body[0]["owner_name"], "admin");
+
+    let body = app.call(get("/servers/live")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("Test Server")]);
+    assert_eq!(body[0]["online"], false);
+    assert_eq!(body[0]["last_heartbeat"], json!(null));
+
+    app.finish().await;
+}
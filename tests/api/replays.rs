@@ -0,0 +1,145 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use actix_web::web::Bytes;
+use serde_json::json;
+use crate::harness::{hash_token, TestApp, ALPHA, MODERATOR};
+
+fn upload(app: &TestApp, run_id: u64, data: &[u8]) -> TestRequest {
+    TestRequest::post()
+        .uri(&format!("/runs/{run_id}/replay"))
+        .insert_header(app.server())
+        .set_payload(Bytes::copy_from_slice(data))
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn replays_of_pbs_are_stored() {
+    let app = TestApp::start().await;
+
+    let body = app.call(upload(&app, 105, b"replay of 105")).await.expect(StatusCode::OK);
+    assert_eq!(body["run_id"], 105);
+    assert_eq!(body["size"], 13);
+    assert_eq!(body["sha256"], hash_token("replay of 105"));
+
+    let res = app.call(TestRequest::get().uri("/runs/105/replay")).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(&res.body[..], b"replay of 105");
+
+    // Uploading again replaces the file.
+    app.call(upload(&app, 105, b"second take")).await.expect(StatusCode::OK);
+    let res = app.call(TestRequest::get().uri("/runs/105/replay")).await;
+    assert_eq!(&res.body[..], b"second take");
+
+    // Well past the default body limit.
+    let large = vec![7; 1024 * 1024];
+    let body = app.call(upload(&app, 105, &large)).await.expect(StatusCode::OK);
+    assert_eq!(body["size"], large.len());
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn record_replays_follow_the_leaderboard() {
+    let app = TestApp::start().await;
+
+    let record = || TestRequest::get().uri("/replays/record?map=kz_alpha&course=0&mode=KZT&kind=NUB");
+    app.call(record()).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+
+    // Delta's faster run doesn't count, delta is banned.
+    app.call(upload(&app, 111, b"banned")).await.expect(StatusCode::OK);
+    app.call(upload(&app, 110, b"record")).await.expect(StatusCode::OK);
+    let res = app.call(record()).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(&res.body[..], b"record");
+
+    let res = app.call(TestRequest::get().uri("/replays/record?map=kz_pending&course=0&mode=KZT&kind=PRO")).await;
+    res.expect_error(StatusCode::NOT_FOUND, "not_found");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn replays_are_dropped_once_beaten() {
+    let app = TestApp::start().await;
+
+    app.call(upload(&app, 105, b"old pb")).await.expect(StatusCode::OK);
+    let run = TestRequest::post().uri("/runs").insert_header(app.server()).set_json(json!({
+        "map": "kz_alpha",
+        "course": 0,
+        "mode": "KZT",
+        "player_id": ALPHA,
+        "player_name": "alpha",
+        "ticks": 10100,
+        "teleports": 0,
+        "created_at": "2026-02-01T00:00:00Z",
+    }));
+    let body = app.call(run).await.expect(StatusCode::OK);
+    assert_eq!(body["pro_pb"], true);
+
+    app.call(TestRequest::get().uri("/runs/105/replay")).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn replays_of_beaten_pbs_wait_for_the_review() {
+    let app = TestApp::start().await;
+
+    app.call(upload(&app, 105, b"old pb")).await.expect(StatusCode::OK);
+    let run = TestRequest::post().uri("/runs").insert_header(app.server()).set_json(json!({
+        "map": "kz_alpha",
+        "course": 0,
+        "mode": "KZT",
+        "player_id": ALPHA,
+        "player_name": "alpha",
+        "ticks": 8000,
+        "teleports": 0,
+        "created_at": "2026-02-01T00:00:00Z",
+    }));
+    let body = app.call(run).await.expect(StatusCode::OK);
+    assert_eq!(body["flagged"], true);
+    let run_id = body["run_id"].as_u64().unwrap();
+
+    // Both stay until the review decides which one is the PB.
+    app.call(upload(&app, run_id, b"new pb")).await.expect(StatusCode::OK);
+    let res = app.call(TestRequest::get().uri("/runs/105/replay")).await;
+    assert_eq!(&res.body[..], b"old pb");
+
+    let moderator = app.login(MODERATOR).await;
+    let body = app.call(TestRequest::get().uri("/runs/reviews").insert_header(moderator.clone())).await.expect(StatusCode::OK);
+    let approve = TestRequest::post()
+        .uri(&format!("/runs/reviews/{}/approve", body[0]["id"]))
+        .insert_header(moderator)
+        .set_json(json!({}));
+    app.call(approve).await.expect(StatusCode::OK);
+    app.call(TestRequest::get().uri("/runs/105/replay")).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+    let res = app.call(TestRequest::get().uri(&format!("/runs/{run_id}/replay"))).await;
+    assert_eq!(&res.body[..], b"new pb");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn replay_uploads_are_checked() {
+    let app = TestApp::start().await;
+
+    // 101 was beaten by alpha's later runs.
+    app.call(upload(&app, 101, b"old")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(upload(&app, 105, b"")).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    app.call(upload(&app, 999, b"missing")).await.expect_error(StatusCode::NOT_FOUND, "unknown_run");
+
+    // Only the server a run was set on may upload its replay.
+    sqlx::query("UPDATE runs SET server_id = 2 WHERE run_id = 109")
+        .execute(&app.db).await.unwrap();
+    app.call(upload(&app, 109, b"not mine")).await.expect_error(StatusCode::FORBIDDEN, "forbidden");
+    let res = app.call(TestRequest::post().uri("/runs/105/replay").set_payload("anonymous")).await;
+    res.expect_error(StatusCode::UNAUTHORIZED, "missing_token");
+
+    app.call(TestRequest::get().uri("/runs/105/replay")).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+
+    app.finish().await;
+}
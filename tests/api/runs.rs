@@ -0,0 +1,258 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use crate::harness::{pluck, TestApp, ALPHA, BRAVO, CHARLIE, DELTA, ECHO};
+
+fn pb_history(player_id: u64, kind: &str) -> TestRequest {
+    TestRequest::get().uri(&format!(
+        "/get_course_pb_history?player_id={player_id}&map=kz_alpha&course=0&mode=KZT&kind={kind}"
+    ))
+}
+
+fn submit(player_id: u64, ticks: u32, teleports: u32, created_at: &str) -> Value {
+    json!({
+        "map": "kz_alpha",
+        "course": 0,
+        "mode": "KZT",
+        "player_id": player_id,
+        "player_name": "renamed",
+        "ticks": ticks,
+        "teleports": teleports,
+        "created_at": created_at,
+    })
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn pb_history_lists_improvements_newest_first() {
+    let app = TestApp::start().await;
+
+    // 103 is slower than the PB before it, 104 only ties it and 106 is slower again.
+    let body = app.call(pb_history(ALPHA, "NUB")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10500), json!(11000), json!(12000)]);
+    assert_eq!(pluck(&body, "created_at"), [
+        json!("2026-01-05T10:00:00Z"),
+        json!("2026-01-02T10:00:00Z"),
+        json!("2026-01-01T10:00:00Z"),
+    ]);
+    assert_eq!(pluck(&body, "teleports"), [json!(0), json!(3), json!(5)]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn pb_history_of_pro_only_counts_runs_without_teleports() {
+    let app = TestApp::start().await;
+
+    let body = app.call(pb_history(ALPHA, "PRO")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10500), json!(11500)]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn pb_history_ignores_invalidated_runs() {
+    let app = TestApp::start().await;
+
+    // Restoring 107 makes it alpha's newest PB.
+    sqlx::query("UPDATE runs SET invalidated_at = NULL WHERE run_id = 107")
+        .execute(&app.db).await.unwrap();
+    let body = app.call(pb_history(ALPHA, "NUB")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10000), json!(10500), json!(11000), json!(12000)]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn pb_history_of_players_without_runs_is_empty() {
+    let app = TestApp::start().await;
+
+    let body = app.call(pb_history(ECHO, "NUB")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+    // Bravo only has a run with teleports.
+    let body = app.call(TestRequest::get().uri(&format!(
+        "/get_course_pb_history?player_id={BRAVO}&map=kz_alpha&course=1&mode=KZT&kind=PRO"
+    ))).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn pb_history_rejects_unknown_courses() {
+    let app = TestApp::start().await;
+
+    let uri = |map: &str, course: u32, mode: &str| TestRequest::get().uri(&format!(
+        "/get_course_pb_history?player_id={ALPHA}&map={map}&course={course}&mode={mode}&kind=NUB"
+    ));
+    app.call(uri("kz_nope", 0, "KZT")).await.expect_error(StatusCode::NOT_FOUND, "unknown_map");
+    app.call(uri("kz_alpha", 7, "KZT")).await.expect_error(StatusCode::NOT_FOUND, "unknown_course");
+    app.call(uri("kz_alpha", 0, "XYZ")).await.expect_error(StatusCode::NOT_FOUND, "unknown_mode");
+    app.call(uri("kz_alpha", 1, "SKZ")).await.expect_error(StatusCode::NOT_FOUND, "course_not_available_in_mode");
+    let res = app.call(TestRequest::get().uri("/get_course_pb_history?map=kz_alpha")).await;
+    res.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn maptop_ranks_each_players_best_run() {
+    let app = TestApp::start().await;
+
+    let uri = "/get_maptop?map=kz_alpha&course=0&mode=KZT&kind=NUB";
+    let body = app.call(TestRequest::get().uri(uri)).await.expect(StatusCode::OK);
+    // Delta is banned and alpha's 10000 is invalidated. Alpha and charlie tie.
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(ALPHA), json!(CHARLIE)]);
+    assert_eq!(pluck(&body, "rank"), [json!(1), json!(2), json!(2)]);
+    assert_eq!(pluck(&body, "ticks"), [json!(9800), json!(10500), json!(10500)]);
+    assert_eq!(pluck(&body, "run_id"), [json!(110), json!(105), json!(109)]);
+
+    let uri = "/get_maptop?map=kz_alpha&course=0&mode=KZT&kind=PRO";
+    let body = app.call(TestRequest::get().uri(uri)).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(ALPHA), json!(CHARLIE)]);
+    assert_eq!(pluck(&body, "ticks"), [json!(10200), json!(10500), json!(10500)]);
+
+    // An earlier run tying alpha's PB that waits for a review doesn't take its place.
+    sqlx::query("INSERT INTO runs (run_id, player_id, filter_id, server_id, ticks, teleports, created_at) VALUES (120, ?, 1, 1, 10500, 0, '2026-01-04 12:00:00')")
+        .bind(ALPHA)
+        .execute(&app.db).await.unwrap();
+    sqlx::query("INSERT INTO run_reviews (run_id, reason) VALUES (120, 'test')")
+        .execute(&app.db).await.unwrap();
+    let body = app.call(TestRequest::get().uri(uri)).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "run_id"), [json!(108), json!(105), json!(109)]);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn maptop_pages_and_filters() {
+    let app = TestApp::start().await;
+
+    let page = |query: &str| TestRequest::get().uri(&format!("/get_maptop?map=kz_alpha&course=0&mode=KZT&kind=NUB&{query}"));
+    let body = app.call(page("limit=1&offset=1")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(ALPHA)]);
+
+    // One player is faster than charlie, so the page starts at the top.
+    let body = app.call(page(&format!("limit=2&player_id={CHARLIE}"))).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(ALPHA)]);
+
+    // Before bravo's 9800, bravo's best was 10200.
+    let body = app.call(page("to=2026-01-10T00:00:00Z")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(10200), json!(10500), json!(10500)]);
+    let body = app.call(page("from=2026-01-09T00:00:00Z")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "player_id"), [json!(BRAVO), json!(CHARLIE)]);
+    let body = app.call(page("server_id=2")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    app.call(page(&format!("player_id={ECHO}"))).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+    // Banned players aren't on the list to center it on.
+    app.call(page(&format!("player_id={DELTA}"))).await.expect_error(StatusCode::NOT_FOUND, "not_found");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn record_history_lists_every_record() {
+    let app = TestApp::start().await;
+
+    let uri = "/get_course_record_history?map=kz_alpha&course=0&mode=KZT&kind=NUB";
+    let body = app.call(TestRequest::get().uri(uri)).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "ticks"), [json!(12000), json!(11000), json!(10500), json!(10200), json!(9800)]);
+    assert_eq!(pluck(&body, "player_id"), [json!(ALPHA), json!(ALPHA), json!(ALPHA), json!(BRAVO), json!(BRAVO)]);
+    let records = body.as_array().unwrap();
+    assert_eq!(records[0]["beaten_at"], "2026-01-02T10:00:00Z");
+    assert_eq!(records[0]["held_for"], 86400);
+    assert_eq!(records[4]["beaten_at"], Value::Null);
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn submitted_runs_report_pbs_and_records() {
+    let app = TestApp::start().await;
+
+    let post = |body: Value| TestRequest::post().uri("/runs").insert_header(app.server()).set_json(body);
+    let body = app.call(post(submit(ALPHA, 10400, 0, "2026-02-01T00:00:00Z"))).await.expect(StatusCode::OK);
+    assert_eq!(body["nub_pb"], true);
+    assert_eq!(body["pro_pb"], true);
+    assert_eq!(body["nub_record"], false);
+    assert_eq!(body["pro_record"], false);
+    assert_eq!(body["flagged"], false);
+
+    // Delta's faster 9000 doesn't count, delta is banned.
+    let body = app.call(post(submit(ALPHA, 9700, 1, "2026-02-02T00:00:00Z"))).await.expect(StatusCode::OK);
+    assert_eq!(body["nub_pb"], true);
+    assert_eq!(body["nub_record"], true);
+    assert_eq!(body["flagged"], false);
+    let body = app.call(post(submit(ALPHA, 8900, 1, "2026-02-03T00:00:00Z"))).await.expect(StatusCode::OK);
+    let run_id = body["run_id"].as_u64().unwrap();
+    assert_eq!(body["nub_pb"], true);
+    assert_eq!(body["pro_pb"], false);
+    assert_eq!(body["nub_record"], true);
+
+    let (name,): (String,) = sqlx::query_as("SELECT name FROM players WHERE player_id = ?")
+        .bind(ALPHA)
+        .fetch_one(&app.db).await.unwrap();
+    assert_eq!(name, "renamed");
+    let (server_id,): (u32,) = sqlx::query_as("SELECT server_id FROM runs WHERE run_id = ?")
+        .bind(run_id)
+        .fetch_one(&app.db).await.unwrap();
+    assert_eq!(server_id, 1);
+
+    let history = app.call(pb_history(ALPHA, "NUB")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&history, "ticks")[0], json!(8900));
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn submitted_runs_are_checked() {
+    let app = TestApp::start().await;
+
+    let post = |body: Value| TestRequest::post().uri("/runs").insert_header(app.server()).set_json(body);
+
+    let res = app.call(TestRequest::post().uri("/runs").set_json(submit(ALPHA, 10400, 0, "2026-02-01T00:00:00Z"))).await;
+    res.expect_error(StatusCode::UNAUTHORIZED, "missing_token");
+    let res = app.call(TestRequest::post().uri("/runs")
+        .insert_header(("X-Server-Token", crate::harness::DISABLED_SERVER_TOKEN))
+        .set_json(submit(ALPHA, 10400, 0, "2026-02-01T00:00:00Z"))).await;
+    res.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+
+    app.call(post(submit(DELTA, 10400, 0, "2026-02-01T00:00:00Z"))).await
+        .expect_error(StatusCode::FORBIDDEN, "player_banned");
+    app.call(post(submit(ALPHA, 100, 0, "2026-02-01T00:00:00Z"))).await
+        .expect_error(StatusCode::UNPROCESSABLE_ENTITY, "implausible_run");
+    // Same as run 105.
+    app.call(post(submit(ALPHA, 10500, 0, "2026-01-05T10:00:00Z"))).await
+        .expect_error(StatusCode::CONFLICT, "duplicate_run");
+
+    // Servers may send the SteamID in any of its formats.
+    let mut steam2 = submit(ALPHA, 10400, 0, "2026-02-01T00:00:00Z");
+    steam2["player_id"] = json!("STEAM_1:0:5");
+    let body = app.call(post(steam2)).await.expect(StatusCode::OK);
+    assert_eq!(body["nub_pb"], true);
+    let mut invalid = submit(ALPHA, 10400, 0, "2026-02-02T00:00:00Z");
+    invalid["player_id"] = json!("STEAM_0:2:5");
+    app.call(post(invalid)).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+
+    // Beating the record by more than 10% is stored but waits for a review.
+    let body = app.call(post(submit(CHARLIE, 7500, 0, "2026-02-01T00:00:00Z"))).await.expect(StatusCode::OK);
+    assert_eq!(body["nub_pb"], true);
+    assert_eq!(body["nub_record"], false);
+    assert_eq!(body["flagged"], true);
+    let (reviews,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM run_reviews WHERE run_id = ?")
+        .bind(body["run_id"].as_u64().unwrap())
+        .fetch_one(&app.db).await.unwrap();
+    assert_eq!(reviews, 1);
+
+    app.finish().await;
+}
@@ -0,0 +1,26 @@
+use api::schema;
+use crate::harness::TestApp;
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn baseline_adopts_databases_from_before_the_migrations() {
+    let app = TestApp::start().await;
+
+    let versions = || sqlx::query_as::<_, (i64,)>("SELECT version FROM _sqlx_migrations ORDER BY version").fetch_all(&app.db);
+    let initial = schema::MIGRATOR.iter().next().unwrap().version;
+    schema::baseline(&app.db).await.expect_err("a migrated database has nothing to baseline");
+
+    // The tables are all there, so it looks like a database created by hand.
+    sqlx::query("DELETE FROM _sqlx_migrations").execute(&app.db).await.unwrap();
+    schema::check(&app.db).await.expect_err("databases without a history aren't up to date");
+    sqlx::query("ALTER TABLE runs DROP KEY idx_runs__createdat").execute(&app.db).await.unwrap();
+    let e = schema::baseline(&app.db).await.expect_err("a key is missing");
+    assert!(e.to_string().contains("idx_runs__createdat"), "{e}");
+    assert_eq!(versions().await.unwrap(), []);
+
+    sqlx::query("ALTER TABLE runs ADD KEY idx_runs__createdat (created_at)").execute(&app.db).await.unwrap();
+    schema::baseline(&app.db).await.unwrap();
+    assert_eq!(versions().await.unwrap(), [(initial,)]);
+
+    app.finish().await;
+}
@@ -0,0 +1,176 @@
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use serde_json::{json, Value};
+use crate::harness::{pluck, TestApp, ADMIN, ALPHA, DELTA, DISABLED_SERVER_TOKEN, MODERATOR, SERVER_TOKEN};
+
+fn heartbeat(token: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/servers/heartbeat")
+        .insert_header(("X-Server-Token", token.to_owned()))
+        .set_json(json!({
+            "map": "kz_alpha",
+            "player_count": 5,
+            "max_players": 24,
+            "plugin_version": "1.2.3",
+        }))
+}
+
+fn jump(player_id: u64, distance: f64, binded: bool) -> Value {
+    json!({
+        "mode": "KZT",
+        "jump_type": "longjump",
+        "player_id": player_id,
+        "player_name": "jumper",
+        "distance": distance,
+        "strafes": 6,
+        "sync": 90.0,
+        "pre_speed": 276.0,
+        "max_speed": 300.0,
+        "airtime": 60,
+        "binded": binded,
+        "created_at": "2026-02-01T00:00:00Z",
+    })
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn heartbeats_bring_servers_online() {
+    let app = TestApp::start().await;
+
+    app.call(heartbeat(SERVER_TOKEN)).await.expect(StatusCode::NO_CONTENT);
+    // The second one updates the same row.
+    app.call(heartbeat(SERVER_TOKEN)).await.expect(StatusCode::NO_CONTENT);
+    let body = app.call(TestRequest::get().uri("/servers/live")).await.expect(StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["online"], true);
+    assert_eq!(body[0]["map"], "kz_alpha");
+    assert_eq!(body[0]["player_count"], 5);
+    assert_eq!(body[0]["plugin_version"], "1.2.3");
+
+    app.call(heartbeat(DISABLED_SERVER_TOKEN)).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    app.call(heartbeat("not-a-token")).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn registered_servers_get_a_token() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let register = |body: Value| TestRequest::post().uri("/servers").insert_header(admin.clone()).set_json(body);
+    let body = app.call(register(json!({ "name": " New Server ", "owner_id": ALPHA, "ip": "192.0.2.1", "port": 27015, "region": "EU" }))).await
+        .expect(StatusCode::OK);
+    let server_id = body["server_id"].as_u64().unwrap();
+    let token = body["token"].as_str().unwrap().to_owned();
+    app.call(heartbeat(&token)).await.expect(StatusCode::NO_CONTENT);
+
+    // Rotating the token locks out the old one.
+    let rotate = TestRequest::post().uri(&format!("/servers/{server_id}/token")).insert_header(admin.clone());
+    let body = app.call(rotate).await.expect(StatusCode::OK);
+    assert_eq!(body["server_id"], server_id);
+    let new_token = body["token"].as_str().unwrap();
+    app.call(heartbeat(&token)).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    app.call(heartbeat(new_token)).await.expect(StatusCode::NO_CONTENT);
+
+    let body = app.call(TestRequest::get().uri("/servers")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("New Server"), json!("Test Server")]);
+    assert_eq!(body[0]["owner_name"], "alpha");
+
+    app.call(register(json!({ "name": "  " }))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    for invalid in [
+        json!({ "name": "Server", "ip": "not an address" }),
+        json!({ "name": "Server", "port": 0 }),
+        json!({ "name": "Server", "region": "somewhere far too long" }),
+    ] {
+        app.call(register(invalid)).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    }
+    let rotate = TestRequest::post().uri("/servers/999/token").insert_header(admin);
+    app.call(rotate).await.expect_error(StatusCode::NOT_FOUND, "unknown_server");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn disabled_servers_are_locked_out() {
+    let app = TestApp::start().await;
+
+    let admin = app.login(ADMIN).await;
+    let post = |uri: &str| TestRequest::post().uri(uri).insert_header(admin.clone());
+
+    app.call(post("/servers/1/disable")).await.expect(StatusCode::NO_CONTENT);
+    app.call(heartbeat(SERVER_TOKEN)).await.expect_error(StatusCode::UNAUTHORIZED, "invalid_token");
+    let body = app.call(TestRequest::get().uri("/servers")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+    let body = app.call(TestRequest::get().uri("/servers/live")).await.expect(StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    app.call(post("/servers/2/enable")).await.expect(StatusCode::NO_CONTENT);
+    app.call(heartbeat(DISABLED_SERVER_TOKEN)).await.expect(StatusCode::NO_CONTENT);
+
+    let body = app.call(TestRequest::get().uri("/servers/all").insert_header(admin.clone())).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "name"), [json!("Old Server"), json!("Test Server")]);
+    assert_eq!(pluck(&body, "disabled"), [json!(false), json!(true)]);
+
+    app.call(post("/servers/999/disable")).await.expect_error(StatusCode::NOT_FOUND, "unknown_server");
+    app.call(post("/servers/999/enable")).await.expect_error(StatusCode::NOT_FOUND, "unknown_server");
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn managing_servers_needs_permission() {
+    let app = TestApp::start().await;
+
+    let moderator = app.login(MODERATOR).await;
+    let forbidden = [
+        TestRequest::get().uri("/servers/all"),
+        TestRequest::post().uri("/servers").set_json(json!({ "name": "Mine" })),
+        TestRequest::post().uri("/servers/1/token"),
+        TestRequest::post().uri("/servers/1/disable"),
+        TestRequest::post().uri("/servers/2/enable"),
+    ];
+    for req in forbidden {
+        app.call(req.insert_header(moderator.clone())).await.expect_error(StatusCode::FORBIDDEN, "forbidden");
+    }
+
+    app.finish().await;
+}
+
+#[actix_web::test]
+#[ignore = "needs a database server, run with --ignored"]
+async fn submitted_jumpstats_report_pbs() {
+    let app = TestApp::start().await;
+
+    let submit = |body: Value| TestRequest::post().uri("/jumpstats").insert_header(app.server()).set_json(body);
+
+    // Alpha's longjumps are 250.5 unbinded and 260 binded.
+    let body = app.call(submit(jump(ALPHA, 255.0, false))).await.expect(StatusCode::OK);
+    assert_eq!(body["unbinded_pb"], true);
+    assert_eq!(body["binded_pb"], false);
+    let body = app.call(submit(jump(ALPHA, 265.0, true))).await.expect(StatusCode::OK);
+    assert_eq!(body["unbinded_pb"], false);
+    assert_eq!(body["binded_pb"], true);
+    let body = app.call(submit(jump(ALPHA, 270.0, false))).await.expect(StatusCode::OK);
+    assert_eq!(body["unbinded_pb"], true);
+    assert_eq!(body["binded_pb"], true);
+
+    let body = app.call(TestRequest::get().uri("/jumpstats?mode=KZT&jump_type=longjump")).await.expect(StatusCode::OK);
+    assert_eq!(pluck(&body, "distance")[0], 270.0);
+
+    app.call(submit(jump(DELTA, 300.0, false))).await.expect_error(StatusCode::FORBIDDEN, "player_banned");
+    app.call(submit(jump(ALPHA, -1.0, false))).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let mut bad_sync = jump(ALPHA, 250.0, false);
+    bad_sync["sync"] = json!(120.0);
+    app.call(submit(bad_sync)).await.expect_error(StatusCode::BAD_REQUEST, "bad_request");
+    let mut bad_mode = jump(ALPHA, 250.0, false);
+    bad_mode["mode"] = json!("XYZ");
+    app.call(submit(bad_mode)).await.expect_error(StatusCode::NOT_FOUND, "unknown_mode");
+    app.call(TestRequest::post().uri("/jumpstats").set_json(jump(ALPHA, 250.0, false))).await
+        .expect_error(StatusCode::UNAUTHORIZED, "missing_token");
+
+    app.finish().await;
+}
@@ -0,0 +1,85 @@
+-- Shared data for the integration tests. Times are UTC, every test connection runs in +00:00.
+--
+-- Players 1 and 2 are staff, 10 to 14 play. 13 is banned, 12 had a ban that ran out and 14
+-- has no runs at all.
+
+INSERT INTO players (player_id, name, search_relevance) VALUES
+    (1, 'admin', 0),
+    (2, 'moderator', 0),
+    (10, 'alpha', 30),
+    (11, 'bravo', 20),
+    (12, 'charlie', 10),
+    (13, 'delta', 5),
+    (14, 'echo', 0);
+
+INSERT INTO player_roles (player_id, role_id)
+SELECT 1, r.role_id FROM roles r WHERE r.name = 'admin'
+UNION ALL
+SELECT 2, r.role_id FROM roles r WHERE r.name = 'moderator';
+
+INSERT INTO bans (player_id, ban_type, reason, banned_by, created_at, expires_at) VALUES
+    (13, 'bhop_hack', 'perfect bhops', 1, '2026-01-12 00:00:00', NULL),
+    (12, 'other', 'expired ban', 1, '2025-01-01 00:00:00', '2025-02-01 00:00:00');
+
+INSERT INTO maps (map_id, name, search_tags, validated, created_at, workshop_id, submitted_by) VALUES
+    (1, 'kz_alpha', 'kz alpha', TRUE, '2025-12-01 00:00:00', 1001, 10),
+    (2, 'kz_bravo', 'kz bravo', TRUE, '2025-12-02 00:00:00', 1002, 11),
+    (3, 'kz_pending', 'kz pending', FALSE, '2025-12-03 00:00:00', 1003, 10);
+
+INSERT INTO mappers (map_id, player_id) VALUES
+    (1, 10),
+    (2, 11),
+    (3, 10);
+
+INSERT INTO courses (course_id, map_id, num) VALUES
+    (1, 1, 0),
+    (2, 1, 1),
+    (3, 2, 0),
+    (4, 3, 0);
+
+INSERT INTO filters (filter_id, course_id, mode_id, nub_tier, pro_tier) VALUES
+    (1, 1, 1, 3, 4),
+    (2, 1, 2, 3, 4),
+    (3, 2, 1, 5, 6),
+    (4, 3, 1, 1, 2),
+    (5, 4, 1, NULL, NULL);
+
+INSERT INTO servers (server_id, name, owner_id, ip, port, region, token_hash, disabled) VALUES
+    (1, 'Test Server', 1, '127.0.0.1', 27015, 'EU', SHA2('server-token', 256), FALSE),
+    (2, 'Old Server', 1, '127.0.0.2', 27015, 'NA', SHA2('disabled-server-token', 256), TRUE);
+
+-- kz_alpha course 0 in KZT. Alpha (10) improves over time; the PB history tests rely on
+-- the exact order:
+--   101 12000 ticks, NUB PB
+--   102 11000 ticks, NUB PB
+--   103 11500 ticks, slower than the NUB PB but alpha's first PRO run
+--   104 11000 ticks again, ties 102 and must not show up twice
+--   105 10500 ticks, NUB and PRO PB
+--   106 10800 ticks, slower
+--   107 10000 ticks, would be a PB but is invalidated
+INSERT INTO runs (run_id, player_id, filter_id, server_id, ticks, teleports, created_at, invalidated_at, invalidated_by, invalidation_reason) VALUES
+    (101, 10, 1, 1, 12000, 5, '2026-01-01 10:00:00', NULL, NULL, NULL),
+    (102, 10, 1, 1, 11000, 3, '2026-01-02 10:00:00', NULL, NULL, NULL),
+    (103, 10, 1, 1, 11500, 0, '2026-01-03 10:00:00', NULL, NULL, NULL),
+    (104, 10, 1, 1, 11000, 2, '2026-01-04 10:00:00', NULL, NULL, NULL),
+    (105, 10, 1, 1, 10500, 0, '2026-01-05 10:00:00', NULL, NULL, NULL),
+    (106, 10, 1, 1, 10800, 1, '2026-01-06 10:00:00', NULL, NULL, NULL),
+    (107, 10, 1, 1, 10000, 4, '2026-01-07 10:00:00', '2026-01-08 00:00:00', 1, 'skipped the course'),
+    (108, 11, 1, 1, 10200, 0, '2026-01-08 10:00:00', NULL, NULL, NULL),
+    (109, 12, 1, 1, 10500, 0, '2026-01-09 10:00:00', NULL, NULL, NULL),
+    (110, 11, 1, 1, 9800, 2, '2026-01-10 10:00:00', NULL, NULL, NULL),
+    (111, 13, 1, 1, 9000, 0, '2026-01-11 10:00:00', NULL, NULL, NULL),
+    -- kz_alpha course 0 in SKZ.
+    (201, 10, 2, 1, 20000, 0, '2026-01-02 12:00:00', NULL, NULL, NULL),
+    -- kz_alpha course 1 in KZT.
+    (301, 11, 3, 1, 15000, 1, '2026-01-03 12:00:00', NULL, NULL, NULL),
+    -- kz_bravo in KZT.
+    (401, 10, 4, 1, 8000, 0, '2026-01-04 12:00:00', NULL, NULL, NULL),
+    (402, 11, 4, 1, 8200, 0, '2026-01-05 12:00:00', NULL, NULL, NULL);
+
+INSERT INTO jumpstats (player_id, mode_id, server_id, jump_type, distance, strafes, sync, pre_speed, max_speed, airtime, binded, created_at) VALUES
+    (10, 1, 1, 'longjump', 250.5, 6, 80.0, 276.0, 300.0, 60, FALSE, '2026-01-01 11:00:00'),
+    (10, 1, 1, 'longjump', 260.0, 7, 85.0, 278.0, 310.0, 62, TRUE, '2026-01-02 11:00:00'),
+    (10, 1, 1, 'bhop', 240.0, 4, 75.0, 290.0, 320.0, 55, FALSE, '2026-01-03 11:00:00'),
+    (11, 1, 1, 'longjump', 255.0, 6, 82.0, 277.0, 305.0, 61, FALSE, '2026-01-04 11:00:00'),
+    (13, 1, 1, 'longjump', 290.0, 9, 99.0, 280.0, 350.0, 70, FALSE, '2026-01-05 11:00:00');